@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+/// Connectivity information of a triangle mesh.
+#[derive(Debug, Clone)]
+pub struct Adjacency {
+    /// The sorted neighbors of every vertex, i.e. the vertices it shares an edge with.
+    pub vertex_neighbors: Vec<Vec<u32>>,
+    /// For every triangle the triangles on the other side of its edges (`i0-i1`, `i1-i2` and
+    /// `i2-i0`); `None` if the edge doesn't have exactly one other triangle.
+    pub triangle_neighbors: Vec<[Option<u32>; 3]>,
+    /// The triangles containing each edge; the key is the edge's vertex indices, smaller first.
+    pub edge_triangles: HashMap<(u32, u32), Vec<u32>>,
+}
+
+/// Builds the vertex-to-vertex and triangle-to-triangle adjacency of a triangle list.
+pub fn build_adjacency(indices: &[u32], vertex_count: usize) -> Adjacency {
+    debug_assert_eq!(indices.len() % 3, 0);
+
+    let mut vertex_neighbors = vec![Vec::new(); vertex_count];
+    let mut edge_triangles: HashMap<(u32, u32), Vec<u32>> = HashMap::new();
+    for (triangle, face) in indices.chunks(3).enumerate() {
+        for &(a, b) in &edges(face) {
+            vertex_neighbors[a as usize].push(b);
+            vertex_neighbors[b as usize].push(a);
+            edge_triangles
+                .entry(edge_key(a, b))
+                .or_insert_with(Vec::new)
+                .push(triangle as u32);
+        }
+    }
+    for neighbors in &mut vertex_neighbors {
+        neighbors.sort();
+        neighbors.dedup();
+    }
+
+    let triangle_neighbors = indices
+        .chunks(3)
+        .enumerate()
+        .map(|(triangle, face)| {
+            let mut neighbors = [None; 3];
+            for (neighbor, &(a, b)) in neighbors.iter_mut().zip(&edges(face)) {
+                if let [t0, t1] = edge_triangles[&edge_key(a, b)][..] {
+                    *neighbor = Some(if t0 == triangle as u32 { t1 } else { t0 });
+                }
+            }
+            neighbors
+        })
+        .collect();
+
+    Adjacency {
+        vertex_neighbors,
+        triangle_neighbors,
+        edge_triangles,
+    }
+}
+
+/// The triangles of a triangle list around every vertex, in compressed form: the triangles of
+/// vertex `i` are `triangles[offsets[i]..offsets[i + 1]]`, in index buffer order. Returns
+/// `(offsets, triangles)`.
+pub fn vertex_triangles(indices: &[u32], vertex_count: usize) -> (Vec<u32>, Vec<u32>) {
+    let mut offsets = vec![0; vertex_count + 1];
+    for &index in indices {
+        offsets[index as usize + 1] += 1;
+    }
+    for i in 0..vertex_count {
+        offsets[i + 1] += offsets[i];
+    }
+    let mut next = offsets.clone();
+    let mut triangles = vec![0; indices.len()];
+    for (i, &index) in indices.iter().enumerate() {
+        triangles[next[index as usize] as usize] = (i / 3) as u32;
+        next[index as usize] += 1;
+    }
+    (offsets, triangles)
+}
+
+fn edges(face: &[u32]) -> [(u32, u32); 3] {
+    let (i0, i1, i2) = (face[0], face[1], face[2]);
+    [(i0, i1), (i1, i2), (i2, i0)]
+}
+
+fn edge_key(a: u32, b: u32) -> (u32, u32) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::icosphere::icosphere;
+
+    #[test]
+    fn closed_mesh_has_two_triangles_per_edge() {
+        let (vertices, indices) = icosphere(2);
+        let adjacency = build_adjacency(&indices, vertices.len());
+        // E = 3F / 2 on a closed triangle mesh
+        assert_eq!(adjacency.edge_triangles.len(), indices.len() / 2);
+        for (edge, triangles) in &adjacency.edge_triangles {
+            assert_eq!(triangles.len(), 2, "edge {:?}", edge);
+        }
+        for neighbors in &adjacency.triangle_neighbors {
+            assert!(neighbors.iter().all(Option::is_some));
+        }
+    }
+
+    #[test]
+    fn vertex_triangles_list_every_triangle_of_a_vertex() {
+        // Two triangles sharing the edge 1-2, and an unused vertex 4
+        let (offsets, triangles) = vertex_triangles(&[0, 1, 2, 2, 1, 3], 5);
+        assert_eq!(offsets, [0, 1, 3, 5, 6, 6]);
+        assert_eq!(triangles, [0, 0, 1, 0, 1, 1]);
+    }
+}
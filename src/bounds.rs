@@ -0,0 +1,82 @@
+use cgmath::Vector3;
+
+use crate::vertex::Vertex;
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) / 2.
+    }
+
+    /// The 8 corners; bit 0/1/2 of the index selects `max` instead of `min` for x/y/z.
+    pub fn corners(&self) -> [Vector3<f32>; 8] {
+        let mut corners = [self.min; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            if i & 1 != 0 {
+                corner.x = self.max.x;
+            }
+            if i & 2 != 0 {
+                corner.y = self.max.y;
+            }
+            if i & 4 != 0 {
+                corner.z = self.max.z;
+            }
+        }
+        corners
+    }
+
+    /// The 12 edges as pairs of corners, e.g. for drawing the box as a line list.
+    pub fn edges(&self) -> [(Vector3<f32>, Vector3<f32>); 12] {
+        let c = self.corners();
+        [
+            // Along the x axis
+            (c[0], c[1]),
+            (c[2], c[3]),
+            (c[4], c[5]),
+            (c[6], c[7]),
+            // Along the y axis
+            (c[0], c[2]),
+            (c[1], c[3]),
+            (c[4], c[6]),
+            (c[5], c[7]),
+            // Along the z axis
+            (c[0], c[4]),
+            (c[1], c[5]),
+            (c[2], c[6]),
+            (c[3], c[7]),
+        ]
+    }
+}
+
+/// Computes the bounding box of `vertices`; `None` if there are no vertices.
+pub fn bounding_box(vertices: &[Vertex]) -> Option<Aabb> {
+    let first = Vector3::from(vertices.first()?.position());
+    let aabb = vertices.iter().skip(1).fold(
+        Aabb {
+            min: first,
+            max: first,
+        },
+        |aabb, vertex| {
+            let p = vertex.position();
+            Aabb {
+                min: Vector3::new(
+                    aabb.min.x.min(p[0]),
+                    aabb.min.y.min(p[1]),
+                    aabb.min.z.min(p[2]),
+                ),
+                max: Vector3::new(
+                    aabb.max.x.max(p[0]),
+                    aabb.max.y.max(p[1]),
+                    aabb.max.z.max(p[2]),
+                ),
+            }
+        },
+    );
+    Some(aabb)
+}
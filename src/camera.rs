@@ -0,0 +1,143 @@
+use cgmath::{Angle, Deg, InnerSpace, Matrix4, Point3, Quaternion, Rad, Rotation3, Vector3};
+
+/// Keeps the camera from flipping over when looking straight up or down.
+const MAX_PITCH: Deg<f32> = Deg(89.0);
+
+/// A free-flying perspective camera with FPS-style mouse look.
+///
+/// In camera space the camera looks along +z with +x to the right and +y up on screen. The view
+/// matrix uses -y as its up vector because the projection doesn't flip y for Vulkan's framebuffer
+/// coordinates.
+///
+/// The orientation is rebuilt from a yaw around the world's y axis and a pitch around the camera's
+/// x axis, so it never rolls.
+pub struct Camera {
+    pub position: Point3<f32>,
+    yaw: Rad<f32>,
+    /// Always within ±[`MAX_PITCH`].
+    pitch: Rad<f32>,
+    /// Vertical field of view.
+    pub fov: Rad<f32>,
+    pub near: f32,
+    pub far: f32,
+    /// Mouse look sensitivity in degrees per pixel.
+    pub sensitivity: f32,
+    /// Width divided by height of the viewport.
+    aspect: f32,
+}
+
+impl Camera {
+    /// A camera at `position` looking along +z with a viewport of `dimensions`.
+    pub fn new(position: Point3<f32>, dimensions: [u32; 2], sensitivity: f32) -> Camera {
+        let mut camera = Camera {
+            position,
+            yaw: Rad(0.0),
+            pitch: Rad(0.0),
+            fov: Rad(std::f32::consts::FRAC_PI_2),
+            near: 0.01,
+            far: 100.0,
+            sensitivity,
+            aspect: 1.0,
+        };
+        camera.resize(dimensions);
+        camera
+    }
+
+    /// Adapts the projection to a viewport of `dimensions`, e.g. after the swapchain was recreated.
+    pub fn resize(&mut self, dimensions: [u32; 2]) {
+        self.aspect = dimensions[0] as f32 / dimensions[1].max(1) as f32;
+    }
+
+    /// The rotation from camera space to world space.
+    pub fn orientation(&self) -> Quaternion<f32> {
+        Quaternion::from_angle_y(self.yaw) * Quaternion::from_angle_x(self.pitch)
+    }
+
+    /// The direction the camera looks in, in world space.
+    pub fn forward(&self) -> Vector3<f32> {
+        self.orientation() * Vector3::unit_z()
+    }
+
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        let up = self.orientation() * Vector3::new(0.0, -1.0, 0.0);
+        Matrix4::look_at_dir(self.position, self.forward(), up)
+    }
+
+    pub fn proj_matrix(&self) -> Matrix4<f32> {
+        cgmath::perspective(self.fov, self.aspect, self.near, self.far)
+    }
+
+    /// The distance from the camera along the view direction of a fragment with `depth` in the
+    /// depth buffer, which is the z coordinate after the projection. Inverts the non-linear
+    /// mapping of [`proj_matrix`](Camera::proj_matrix), which puts most of the depth precision
+    /// close to the near plane.
+    pub fn linear_depth(&self, depth: f32) -> f32 {
+        let (near, far) = (self.near, self.far);
+        2.0 * far * near / (far + near - depth * (far - near))
+    }
+
+    /// Moves the camera by `delta` in camera space.
+    pub fn apply_movement(&mut self, delta: Vector3<f32>) {
+        self.position += self.orientation() * delta;
+    }
+
+    /// Rotates the camera by a mouse movement of `dx` and `dy` pixels.
+    pub fn apply_mouse(&mut self, dx: f32, dy: f32) {
+        self.yaw = (self.yaw + Rad::from(Deg(self.sensitivity * dx))).normalize();
+        self.set_pitch(self.pitch + Rad::from(Deg(self.sensitivity * dy)));
+    }
+
+    /// Turns the camera towards `target`. Does nothing when the camera is at `target`.
+    pub fn look_at(&mut self, target: Point3<f32>) {
+        let dir = target - self.position;
+        if dir.magnitude2() < 1e-6 {
+            return;
+        }
+        let dir = dir.normalize();
+        // Straight above or below, the yaw is kept
+        if dir.x.abs() > 1e-6 || dir.z.abs() > 1e-6 {
+            self.yaw = Rad::atan2(dir.x, dir.z);
+        }
+        // A positive pitch turns the camera towards -y
+        self.set_pitch(Rad::asin(-dir.y));
+    }
+
+    fn set_pitch(&mut self, pitch: Rad<f32>) {
+        let max = Rad::from(MAX_PITCH);
+        self.pitch = Rad(pitch.0.max(-max.0).min(max.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pitch_stays_within_limits() {
+        let mut camera = Camera::new(Point3::new(0.0, 0.0, 0.0), [800, 600], 0.1);
+        let max = Rad::from(MAX_PITCH);
+        // Long runs of mouse movement in both directions, with the occasional large jump
+        for i in 0..10_000 {
+            let dy = match (i / 1000) % 2 {
+                0 => 37.0,
+                _ => -53.0,
+            } * if i % 97 == 0 { 100.0 } else { 1.0 };
+            camera.apply_mouse(3.0, dy);
+            assert!(camera.pitch.0.abs() <= max.0, "pitch {:?}", camera.pitch);
+        }
+        // The view still points somewhere sensible when looking straight up or down
+        assert!(camera.forward().y.abs() < 1.0);
+    }
+
+    #[test]
+    fn linear_depth_inverts_the_projection() {
+        let camera = Camera::new(Point3::new(0.0, 0.0, 0.0), [800, 600], 0.1);
+        for &distance in &[camera.near, 0.5, 5.0, 42.0, camera.far] {
+            let point = camera.view_matrix() * (camera.forward() * distance).extend(1.0);
+            let clip = camera.proj_matrix() * point;
+            let depth = clip.z / clip.w;
+            let error = (camera.linear_depth(depth) - distance).abs();
+            assert!(error < 1e-3 * distance, "{} at {}", depth, distance);
+        }
+    }
+}
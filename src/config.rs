@@ -0,0 +1,501 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use vulkano::swapchain::{CompositeAlpha, PresentMode};
+
+use crate::options::{
+    parse_composite_alpha, parse_grid, parse_instance_colors, parse_msaa, parse_present_mode,
+    parse_subdivisions, parse_uv_test_colors, parse_uv_test_tiles, Options,
+};
+
+use crate::instances::InstanceColors;
+
+/// The settings of the application.
+///
+/// The defaults can be overridden by environment variables (see [`Config::from_env`]), which
+/// can be overridden by a config file (see [`Config::with_file`]), which can in turn be
+/// overridden by the command line arguments (see [`Config::override_with`]). [`Config::load`]
+/// applies all of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub alpha: Option<CompositeAlpha>,
+    /// Subdivision level of the most detailed icosphere.
+    pub subdivisions: u8,
+    /// Number of copies of the mesh along each axis.
+    pub grid: u32,
+    /// How the copies of the mesh are tinted.
+    pub instance_colors: InstanceColors,
+    /// Whether the meshes are drawn with a checkerboard texture.
+    pub uv_test: bool,
+    /// Number of squares of the checkerboard along each texture coordinate.
+    pub uv_test_tiles: u32,
+    /// The sRGB colors of the squares of the checkerboard.
+    pub uv_test_colors: [[u8; 3]; 2],
+    pub present_mode: Option<PresentMode>,
+    /// Requested samples per pixel; 1 disables multisampling.
+    pub msaa: u32,
+    pub stats: bool,
+    pub smooth: u32,
+    pub frames: Option<u32>,
+    pub stress: bool,
+    pub timing_csv: Option<PathBuf>,
+    pub gltf: Option<PathBuf>,
+    /// Whether the winding of the imported triangles is reversed instead of detected.
+    pub flip_winding: bool,
+    /// Mouse look sensitivity in degrees per pixel of mouse movement.
+    pub look_sensitivity: f32,
+    /// Camera movement speed in units per second.
+    pub move_speed: f32,
+    /// Maximum change of the camera velocity in units per second².
+    pub acceleration: f32,
+    /// Exponential decay rate of the camera velocity per second when no key is pressed.
+    pub damping: f32,
+    /// Whether the camera movement is accelerated and damped instead of instant.
+    pub smooth_movement: bool,
+    /// Index of the physical device to render with.
+    pub gpu: Option<usize>,
+    /// Whether the normals of the morph are computed on the GPU.
+    pub gpu_normals: bool,
+    /// Writes the icosphere to this OBJ file and exits instead of rendering it.
+    pub export: Option<PathBuf>,
+    /// Writes the depth buffer of the first frame to this PNG file.
+    pub depth_shot: Option<PathBuf>,
+    /// Checks this mesh file and exits instead of rendering.
+    pub validate: Option<PathBuf>,
+    /// The config file that was applied by [`Config::load`], which runtime changes of the
+    /// settings are saved to.
+    pub file: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            alpha: None,
+            subdivisions: 6,
+            grid: 1,
+            instance_colors: InstanceColors::White,
+            uv_test: false,
+            uv_test_tiles: 8,
+            uv_test_colors: [[0xff, 0xff, 0xff], [0x30, 0x30, 0x30]],
+            present_mode: None,
+            msaa: 1,
+            stats: false,
+            smooth: 0,
+            frames: None,
+            stress: false,
+            timing_csv: None,
+            gltf: None,
+            flip_winding: false,
+            look_sensitivity: 0.1,
+            move_speed: 3.0,
+            acceleration: 20.0,
+            damping: 8.0,
+            smooth_movement: false,
+            gpu: None,
+            gpu_normals: false,
+            export: None,
+            depth_shot: None,
+            validate: None,
+            file: None,
+        }
+    }
+}
+
+impl Config {
+    /// Starts from the defaults and applies the following environment variables if they are set:
+    ///
+    /// - `CURVEBALL_ALPHA`: same values as `--alpha`
+    /// - `CURVEBALL_SUBDIVISIONS`: same values as `--subdivisions`
+    /// - `CURVEBALL_GRID`: same values as `--grid`
+    /// - `CURVEBALL_INSTANCE_COLORS`: same values as `--instance-colors`
+    /// - `CURVEBALL_UV_TEST`: `1`/`true` or `0`/`false`; same as passing `--uv-test`
+    /// - `CURVEBALL_UV_TEST_TILES`: same values as `--uv-test-tiles`
+    /// - `CURVEBALL_UV_TEST_COLORS`: same values as `--uv-test-colors`
+    /// - `CURVEBALL_PRESENT_MODE`: same values as `--present-mode`
+    /// - `CURVEBALL_MSAA`: same values as `--msaa`
+    /// - `CURVEBALL_STATS`: `1`/`true` or `0`/`false`; same as passing `--stats`
+    /// - `CURVEBALL_SMOOTH`: same values as `--smooth`
+    /// - `CURVEBALL_FRAMES`: same values as `--frames`
+    /// - `CURVEBALL_STRESS`: `1`/`true` or `0`/`false`; same as passing `--stress`
+    /// - `CURVEBALL_TIMING_CSV`: same values as `--timing-csv`
+    /// - `CURVEBALL_GLTF`: same values as `--gltf`
+    /// - `CURVEBALL_FLIP_WINDING`: `1`/`true` or `0`/`false`; same as passing `--flip-winding`
+    /// - `CURVEBALL_LOOK_SENSITIVITY`: same values as `--look-sensitivity`
+    /// - `CURVEBALL_MOVE_SPEED`: same values as `--move-speed`
+    /// - `CURVEBALL_ACCELERATION`: same values as `--acceleration`
+    /// - `CURVEBALL_DAMPING`: same values as `--damping`
+    /// - `CURVEBALL_SMOOTH_MOVEMENT`: `1`/`true` or `0`/`false`; same as passing
+    ///   `--smooth-movement`
+    /// - `CURVEBALL_GPU`: same values as `--gpu`
+    /// - `CURVEBALL_GPU_NORMALS`: `1`/`true` or `0`/`false`; same as passing `--gpu-normals`
+    /// - `CURVEBALL_EXPORT`: same values as `--export`
+    /// - `CURVEBALL_DEPTH_SHOT`: same values as `--depth-shot`
+    /// - `CURVEBALL_VALIDATE`: same values as `--validate`
+    pub fn from_env() -> Result<Config, String> {
+        Config::default().with_vars(|name| env::var(name).ok())
+    }
+
+    /// Applies the environment, the config file passed with `--config` or `CURVEBALL_CONFIG` if
+    /// there is one, and the command line arguments to the defaults.
+    pub fn load(options: &Options) -> Result<Config, String> {
+        let mut config = Config::from_env()?;
+        let path = options
+            .config
+            .clone()
+            .or_else(|| env::var_os("CURVEBALL_CONFIG").map(PathBuf::from));
+        if let Some(path) = path {
+            config = config.with_file(&path)?;
+            config.file = Some(path);
+        }
+        Ok(config.override_with(options))
+    }
+
+    /// Sets `name` to `value` in the config file at `path`, which is created if it doesn't exist
+    /// yet. The other lines of the file are kept.
+    pub fn save_setting(path: &Path, name: &str, value: &str) -> Result<(), String> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("failed to read {}: {}", path.display(), e)),
+        };
+        fs::write(path, with_setting(&contents, name, value))
+            .map_err(|e| format!("failed to write {}: {}", path.display(), e))
+    }
+
+    /// Applies the settings of a config file with a `name = value` pair per line. The names are
+    /// those of the environment variables without the `CURVEBALL_` prefix, in lowercase, e.g.
+    /// `subdivisions = 4`. Empty lines and lines starting with `#` are ignored.
+    pub fn with_file(self, path: &Path) -> Result<Config, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        self.with_file_contents(&contents)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn with_file_contents(self, contents: &str) -> Result<Config, String> {
+        let mut values = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            let (name, value) = match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => (name.trim(), value.trim()),
+                _ => return Err(format!("line {}: expected `name = value`", number + 1)),
+            };
+            values.push((
+                format!("CURVEBALL_{}", name.to_uppercase().replace('-', "_")),
+                value.to_string(),
+            ));
+        }
+
+        // Every name has to be looked up by `with_vars`, otherwise it's a typo
+        let used = RefCell::new(HashSet::new());
+        let config = self.with_vars(|name| {
+            let value = values.iter().rev().find(|(n, _)| n == name);
+            if value.is_some() {
+                used.borrow_mut().insert(name.to_string());
+            }
+            value.map(|(_, value)| value.clone())
+        })?;
+        let used = used.into_inner();
+        if let Some((name, _)) = values.iter().find(|(name, _)| !used.contains(name)) {
+            return Err(format!(
+                "unknown setting {}",
+                name["CURVEBALL_".len()..].to_lowercase()
+            ));
+        }
+        Ok(config)
+    }
+
+    fn with_vars(self, var: impl Fn(&str) -> Option<String>) -> Result<Config, String> {
+        let mut config = self;
+        if let Some(value) = var("CURVEBALL_ALPHA") {
+            config.alpha =
+                Some(parse_composite_alpha(&value).map_err(|e| format!("CURVEBALL_ALPHA: {}", e))?);
+        }
+        if let Some(value) = var("CURVEBALL_SUBDIVISIONS") {
+            config.subdivisions =
+                parse_subdivisions(&value).map_err(|e| format!("CURVEBALL_SUBDIVISIONS: {}", e))?;
+        }
+        if let Some(value) = var("CURVEBALL_GRID") {
+            config.grid = parse_grid(&value).map_err(|e| format!("CURVEBALL_GRID: {}", e))?;
+        }
+        if let Some(value) = var("CURVEBALL_INSTANCE_COLORS") {
+            config.instance_colors = parse_instance_colors(&value)
+                .map_err(|e| format!("CURVEBALL_INSTANCE_COLORS: {}", e))?;
+        }
+        if let Some(value) = var("CURVEBALL_UV_TEST") {
+            config.uv_test = parse_bool(&value).map_err(|e| format!("CURVEBALL_UV_TEST: {}", e))?;
+        }
+        if let Some(value) = var("CURVEBALL_UV_TEST_TILES") {
+            config.uv_test_tiles = parse_uv_test_tiles(&value)
+                .map_err(|e| format!("CURVEBALL_UV_TEST_TILES: {}", e))?;
+        }
+        if let Some(value) = var("CURVEBALL_UV_TEST_COLORS") {
+            config.uv_test_colors = parse_uv_test_colors(&value)
+                .map_err(|e| format!("CURVEBALL_UV_TEST_COLORS: {}", e))?;
+        }
+        if let Some(value) = var("CURVEBALL_PRESENT_MODE") {
+            config.present_mode = Some(
+                parse_present_mode(&value).map_err(|e| format!("CURVEBALL_PRESENT_MODE: {}", e))?,
+            );
+        }
+        if let Some(value) = var("CURVEBALL_MSAA") {
+            config.msaa = parse_msaa(&value).map_err(|e| format!("CURVEBALL_MSAA: {}", e))?;
+        }
+        if let Some(value) = var("CURVEBALL_STATS") {
+            config.stats = parse_bool(&value).map_err(|e| format!("CURVEBALL_STATS: {}", e))?;
+        }
+        if let Some(value) = var("CURVEBALL_SMOOTH") {
+            config.smooth = value
+                .parse()
+                .map_err(|e| format!("CURVEBALL_SMOOTH: {}", e))?;
+        }
+        if let Some(value) = var("CURVEBALL_FRAMES") {
+            config.frames = Some(
+                value
+                    .parse()
+                    .map_err(|e| format!("CURVEBALL_FRAMES: {}", e))?,
+            );
+        }
+        if let Some(value) = var("CURVEBALL_STRESS") {
+            config.stress = parse_bool(&value).map_err(|e| format!("CURVEBALL_STRESS: {}", e))?;
+        }
+        if let Some(value) = var("CURVEBALL_TIMING_CSV") {
+            config.timing_csv = Some(value.into());
+        }
+        if let Some(value) = var("CURVEBALL_GLTF") {
+            config.gltf = Some(value.into());
+        }
+        if let Some(value) = var("CURVEBALL_FLIP_WINDING") {
+            config.flip_winding =
+                parse_bool(&value).map_err(|e| format!("CURVEBALL_FLIP_WINDING: {}", e))?;
+        }
+        if let Some(value) = var("CURVEBALL_LOOK_SENSITIVITY") {
+            config.look_sensitivity = value
+                .parse()
+                .map_err(|e| format!("CURVEBALL_LOOK_SENSITIVITY: {}", e))?;
+        }
+        if let Some(value) = var("CURVEBALL_MOVE_SPEED") {
+            config.move_speed = value
+                .parse()
+                .map_err(|e| format!("CURVEBALL_MOVE_SPEED: {}", e))?;
+        }
+        if let Some(value) = var("CURVEBALL_ACCELERATION") {
+            config.acceleration = value
+                .parse()
+                .map_err(|e| format!("CURVEBALL_ACCELERATION: {}", e))?;
+        }
+        if let Some(value) = var("CURVEBALL_DAMPING") {
+            config.damping = value
+                .parse()
+                .map_err(|e| format!("CURVEBALL_DAMPING: {}", e))?;
+        }
+        if let Some(value) = var("CURVEBALL_SMOOTH_MOVEMENT") {
+            config.smooth_movement =
+                parse_bool(&value).map_err(|e| format!("CURVEBALL_SMOOTH_MOVEMENT: {}", e))?;
+        }
+        if let Some(value) = var("CURVEBALL_GPU") {
+            config.gpu = Some(value.parse().map_err(|e| format!("CURVEBALL_GPU: {}", e))?);
+        }
+        if let Some(value) = var("CURVEBALL_GPU_NORMALS") {
+            config.gpu_normals =
+                parse_bool(&value).map_err(|e| format!("CURVEBALL_GPU_NORMALS: {}", e))?;
+        }
+        if let Some(value) = var("CURVEBALL_EXPORT") {
+            config.export = Some(value.into());
+        }
+        if let Some(value) = var("CURVEBALL_DEPTH_SHOT") {
+            config.depth_shot = Some(value.into());
+        }
+        if let Some(value) = var("CURVEBALL_VALIDATE") {
+            config.validate = Some(value.into());
+        }
+        Ok(config)
+    }
+
+    /// Overrides every setting that was explicitly passed on the command line.
+    pub fn override_with(mut self, options: &Options) -> Config {
+        if options.alpha.is_some() {
+            self.alpha = options.alpha;
+        }
+        if let Some(subdivisions) = options.subdivisions {
+            self.subdivisions = subdivisions;
+        }
+        if let Some(grid) = options.grid {
+            self.grid = grid;
+        }
+        if let Some(instance_colors) = options.instance_colors {
+            self.instance_colors = instance_colors;
+        }
+        if options.uv_test {
+            self.uv_test = true;
+        }
+        if let Some(uv_test_tiles) = options.uv_test_tiles {
+            self.uv_test_tiles = uv_test_tiles;
+        }
+        if let Some(uv_test_colors) = options.uv_test_colors {
+            self.uv_test_colors = uv_test_colors;
+        }
+        if options.present_mode.is_some() {
+            self.present_mode = options.present_mode;
+        }
+        if let Some(msaa) = options.msaa {
+            self.msaa = msaa;
+        }
+        if options.stats {
+            self.stats = true;
+        }
+        if let Some(smooth) = options.smooth {
+            self.smooth = smooth;
+        }
+        if options.frames.is_some() {
+            self.frames = options.frames;
+        }
+        if options.stress {
+            self.stress = true;
+        }
+        if options.timing_csv.is_some() {
+            self.timing_csv = options.timing_csv.clone();
+        }
+        if options.gltf.is_some() {
+            self.gltf = options.gltf.clone();
+        }
+        if options.flip_winding {
+            self.flip_winding = true;
+        }
+        if let Some(look_sensitivity) = options.look_sensitivity {
+            self.look_sensitivity = look_sensitivity;
+        }
+        if let Some(move_speed) = options.move_speed {
+            self.move_speed = move_speed;
+        }
+        if let Some(acceleration) = options.acceleration {
+            self.acceleration = acceleration;
+        }
+        if let Some(damping) = options.damping {
+            self.damping = damping;
+        }
+        if options.smooth_movement {
+            self.smooth_movement = true;
+        }
+        if options.gpu.is_some() {
+            self.gpu = options.gpu;
+        }
+        if options.gpu_normals {
+            self.gpu_normals = true;
+        }
+        if options.export.is_some() {
+            self.export = options.export.clone();
+        }
+        if options.depth_shot.is_some() {
+            self.depth_shot = options.depth_shot.clone();
+        }
+        if options.validate.is_some() {
+            self.validate = options.validate.clone();
+        }
+        self
+    }
+}
+
+/// Replaces the last line of the config file `contents` that sets `name`, which is the one that
+/// takes effect, or appends one.
+fn with_setting(contents: &str, name: &str, value: &str) -> String {
+    let name = name.replace('-', "_");
+    let sets_name = |line: &str| {
+        let line = line.trim();
+        !line.starts_with('#')
+            && line.contains('=')
+            && line.split('=').next().unwrap().trim().replace('-', "_") == name
+    };
+    let mut lines: Vec<String> = contents.lines().map(String::from).collect();
+    let line = format!("{} = {}", name, value);
+    match lines.iter().rposition(|line| sets_name(line)) {
+        Some(i) => lines[i] = line,
+        None => lines.push(line),
+    }
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+    contents
+}
+
+fn parse_bool(s: &str) -> Result<bool, String> {
+    match s {
+        "1" | "true" => Ok(true),
+        "0" | "false" => Ok(false),
+        _ => Err(format!("expected 1/true or 0/false, got {}", s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use structopt::StructOpt;
+
+    use super::*;
+
+    fn vars<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| {
+            vars.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn later_layers_take_precedence() {
+        let env = [
+            ("CURVEBALL_SUBDIVISIONS", "4"),
+            ("CURVEBALL_GRID", "2"),
+            ("CURVEBALL_MSAA", "2"),
+        ];
+        let file = "# comment\n\ngrid = 3\nmsaa = 1\n";
+        let options = Options::from_iter(&["curveball", "--msaa", "8"]);
+        let config = Config::default()
+            .with_vars(vars(&env))
+            .unwrap()
+            .with_file_contents(file)
+            .unwrap()
+            .override_with(&options);
+
+        let default = Config::default();
+        assert_eq!(config.move_speed, default.move_speed);
+        assert_eq!(config.subdivisions, 4);
+        assert_eq!(config.grid, 3);
+        assert_eq!(config.msaa, 8);
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        assert!(Config::default()
+            .with_vars(vars(&[("CURVEBALL_GRID", "0")]))
+            .is_err());
+        assert!(Config::default()
+            .with_file_contents("subdivisions = 11")
+            .is_err());
+        assert!(Config::default().with_file_contents("grid 3").is_err());
+        assert!(Config::default().with_file_contents("gird = 3").is_err());
+    }
+
+    #[test]
+    fn saved_settings_are_read_back() {
+        let path = env::temp_dir().join(format!("curveball-{}.conf", std::process::id()));
+        fs::write(&path, "# comment\ngrid = 3\nlook-sensitivity = 0.1\n").unwrap();
+        Config::save_setting(&path, "look_sensitivity", "0.25").unwrap();
+        Config::save_setting(&path, "move_speed", "5").unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let config = Config::default().with_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(contents.starts_with("# comment\ngrid = 3\n"));
+        let config = config.unwrap();
+        assert_eq!(config.grid, 3);
+        assert_eq!(config.look_sensitivity, 0.25);
+        assert_eq!(config.move_speed, 5.0);
+    }
+}
@@ -0,0 +1,34 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::vertex::Vertex;
+
+/// Splits the triangles of the mesh apart and moves each of them `distance` along its face
+/// normal. Returns a triangle list with three vertices per triangle and no index buffer, whose
+/// vertices have the face normal of their triangle and keep their texture coordinates.
+///
+/// The face normal points to the side from which the triangle appears counter-clockwise, which is
+/// the outside of an [`icosphere`](crate::icosphere::icosphere). Degenerate triangles aren't moved.
+pub fn explode(vertices: &[Vertex], indices: &[u32], distance: f32) -> Vec<Vertex> {
+    debug_assert_eq!(indices.len() % 3, 0);
+
+    let mut exploded = Vec::with_capacity(indices.len());
+    for face in indices.chunks(3) {
+        let position = |i: u32| Vector3::from(vertices[i as usize].position());
+        let (a, b, c) = (position(face[0]), position(face[1]), position(face[2]));
+        let normal = (b - a).cross(c - a);
+        let normal = if normal.magnitude2() > 0.0 {
+            normal.normalize()
+        } else {
+            Vector3::new(0.0, 0.0, 0.0)
+        };
+        let offset = normal * distance;
+        exploded.extend([a, b, c].iter().zip(face).map(|(&p, &i)| {
+            Vertex::new(
+                (p + offset).into(),
+                normal.into(),
+                vertices[i as usize].uv(),
+            )
+        }));
+    }
+    exploded
+}
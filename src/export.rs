@@ -0,0 +1,27 @@
+use std::io::{self, Write};
+
+use crate::vertex::Vertex;
+
+/// Writes the mesh as a Wavefront OBJ file with a `v` and a `vn` line per vertex and an `f` line
+/// per triangle.
+///
+/// OBJ expects triangles to be counter-clockwise when seen from the outside, like those of the
+/// [`icosphere`](crate::icosphere::icosphere). Indices in the file start at 1.
+pub fn write_obj(vertices: &[Vertex], indices: &[u32], mut writer: impl Write) -> io::Result<()> {
+    debug_assert_eq!(indices.len() % 3, 0);
+
+    writeln!(writer, "# curveball mesh")?;
+    for vertex in vertices {
+        let [x, y, z] = vertex.position();
+        writeln!(writer, "v {} {} {}", x, y, z)?;
+    }
+    for vertex in vertices {
+        let [x, y, z] = vertex.normal();
+        writeln!(writer, "vn {} {} {}", x, y, z)?;
+    }
+    for face in indices.chunks(3) {
+        let (a, b, c) = (face[0] + 1, face[1] + 1, face[2] + 1);
+        writeln!(writer, "f {}//{} {}//{} {}//{}", a, a, b, b, c, c)?;
+    }
+    writer.flush()
+}
@@ -0,0 +1,117 @@
+use std::sync::Arc;
+
+use vulkano::buffer::{BufferUsage, DeviceLocalBuffer, ImmutableBuffer, TypedBufferAccess};
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::descriptor::pipeline_layout::PipelineLayout;
+use vulkano::device::{Device, Queue};
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::pipeline::ComputePipeline;
+use vulkano::sync::GpuFuture;
+
+use curveball::adjacency::vertex_triangles;
+use curveball::vertex::Vertex;
+
+use crate::shaders;
+
+/// Invocations per work group; must match `local_size_x` in `normals.glsl`.
+const WORK_GROUP_SIZE: u32 = 64;
+
+/// The triangles of a mesh and the triangles around each of its vertices, in storage buffers for
+/// [`GpuNormals`]. Deforming the mesh doesn't change them.
+pub struct Topology {
+    indices: Arc<ImmutableBuffer<[u32]>>,
+    offsets: Arc<ImmutableBuffer<[u32]>>,
+    triangles: Arc<ImmutableBuffer<[u32]>>,
+}
+
+impl Topology {
+    /// Uploads the topology of a mesh with `vertex_count` vertices with `queue`. The buffers may
+    /// only be used after the returned future.
+    pub fn new(
+        queue: Arc<Queue>,
+        indices: &[u32],
+        vertex_count: usize,
+    ) -> Result<(Topology, Box<dyn GpuFuture>), DeviceMemoryAllocError> {
+        let (offsets, triangles) = vertex_triangles(indices, vertex_count);
+        let usage = BufferUsage {
+            storage_buffer: true,
+            ..BufferUsage::none()
+        };
+        let upload =
+            |data: Vec<u32>| ImmutableBuffer::from_iter(data.into_iter(), usage, queue.clone());
+        let (indices, indices_upload) = upload(indices.to_vec())?;
+        let (offsets, offsets_upload) = upload(offsets)?;
+        let (triangles, triangles_upload) = upload(triangles)?;
+        let topology = Topology {
+            indices,
+            offsets,
+            triangles,
+        };
+        let upload = indices_upload.join(offsets_upload).join(triangles_upload);
+        Ok((topology, Box::new(upload)))
+    }
+}
+
+/// Computes the normals of deformed meshes like
+/// [`compute_normals`](curveball::mesh::compute_normals), but in a compute shader.
+pub struct GpuNormals {
+    device: Arc<Device>,
+    pipeline: Arc<ComputePipeline<PipelineLayout<shaders::normals_cs::Layout>>>,
+}
+
+impl GpuNormals {
+    pub fn new(device: Arc<Device>) -> Result<GpuNormals, String> {
+        let shader = shaders::normals_cs::Shader::load(device.clone())
+            .map_err(|err| format!("failed to create shader module: {}", err))?;
+        let pipeline = ComputePipeline::new(device.clone(), &shader.main_entry_point(), &())
+            .map_err(|err| format!("failed to create compute pipeline: {}", err))?;
+        Ok(GpuNormals {
+            device,
+            pipeline: Arc::new(pipeline),
+        })
+    }
+
+    /// Records a dispatch that writes `vertices` with their normals recomputed from their
+    /// positions and `topology` into a new device-local buffer, which is returned. It has to be
+    /// recorded outside of a render pass; the command buffer waits for the dispatch with a
+    /// pipeline barrier before the vertex buffer is read by a draw recorded after it.
+    pub fn record(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        vertices: Arc<dyn TypedBufferAccess<Content = [Vertex]> + Send + Sync>,
+        topology: &Topology,
+    ) -> Result<(AutoCommandBufferBuilder, Arc<DeviceLocalBuffer<[Vertex]>>), String> {
+        let device = self.device.clone();
+        let count = vertices.len();
+        let usage = BufferUsage {
+            storage_buffer: true,
+            vertex_buffer: true,
+            ..BufferUsage::none()
+        };
+        let output =
+            DeviceLocalBuffer::array(device.clone(), count, usage, device.active_queue_families())
+                .map_err(|err| format!("failed to create buffer: {}", err))?;
+        let set = PersistentDescriptorSet::start(self.pipeline.clone(), 0)
+            .add_buffer(vertices)
+            .and_then(|set| set.add_buffer(topology.indices.clone()))
+            .and_then(|set| set.add_buffer(topology.offsets.clone()))
+            .and_then(|set| set.add_buffer(topology.triangles.clone()))
+            .and_then(|set| set.add_buffer(output.clone()))
+            .map_err(|err| format!("failed to add a buffer to the descriptor set: {}", err))?
+            .build()
+            .map_err(|err| format!("failed to create descriptor set: {}", err))?;
+        let groups = (count as u32 + WORK_GROUP_SIZE - 1) / WORK_GROUP_SIZE;
+        let builder = builder
+            .dispatch(
+                [groups, 1, 1],
+                self.pipeline.clone(),
+                set,
+                shaders::normals_cs::ty::PushConstants {
+                    vertex_count: count as u32,
+                },
+            )
+            .map_err(|err| format!("failed to record the normal computation: {}", err))?;
+        Ok((builder, output))
+    }
+}
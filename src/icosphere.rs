@@ -1,15 +1,80 @@
 use cgmath::{InnerSpace, Vector3};
 use std::collections::HashMap;
 
+use crate::mesh::compute_normals;
 use crate::vertex::Vertex;
 
+/// The golden ratio, `(1 + √5) / 2`.
+const TAU: f32 = 1.618_034;
+
+/// The 12 vertices of the icosahedron that [`icosphere`] subdivides. The edges are 2 units long.
+pub const ICOSAHEDRON_VERTICES: [[f32; 3]; 12] = [
+    [1., TAU, 0.],
+    [1., -TAU, 0.],
+    [-1., -TAU, 0.],
+    [-1., TAU, 0.],
+    [0., 1., TAU],
+    [0., 1., -TAU],
+    [0., -1., -TAU],
+    [0., -1., TAU],
+    [TAU, 0., 1.],
+    [-TAU, 0., 1.],
+    [-TAU, 0., -1.],
+    [TAU, 0., -1.],
+];
+
+/// The 20 triangles of the icosahedron as indices into [`ICOSAHEDRON_VERTICES`].
+///
+/// The triangles are counter-clockwise when seen from the outside, in the right-handed object
+/// space of the vertices. The subdivided [`icosphere`] keeps this winding.
+#[rustfmt::skip]
+pub const ICOSAHEDRON_INDICES: [u32; 60] = [
+    0, 3, 4, 0, 5, 3, 0, 4, 8, 0, 11, 5, 0, 8, 11,
+    1, 2, 6, 1, 7, 2, 1, 6, 11, 1, 8, 7, 1, 11, 8,
+    2, 10, 6, 2, 7, 9, 2, 9, 10, 3, 9, 4, 3, 5, 10,
+    3, 10, 9, 4, 7, 8, 4, 9, 7, 5, 6, 10, 5, 11, 6,
+];
+
+/// The highest subdivision level that [`icosphere`] accepts. Level 10 already has about 21
+/// million triangles and needs about 1 GB of memory to generate; the vertex count would only
+/// overflow the `u32` indices above level 14.
+pub const MAX_SUBDIVISION_LEVEL: u8 = 10;
+
 /// Generates the vertices and triangle vertex indices of an icosphere with the specified
 /// subidivison level.
 ///
+/// The texture coordinates are a longitude/latitude mapping, see [`spherical_uv`] for its
+/// artifacts.
+///
+/// # Panics
+/// If `subdivison_level` is above [`MAX_SUBDIVISION_LEVEL`].
+///
 /// # References
 /// - <https://github.com/caosdoar/spheres>
 /// - <http://blog.andreaskahler.com/2009/06/creating-icosphere-mesh-in-code.html>
 pub fn icosphere(subdivison_level: u8) -> (Vec<Vertex>, Vec<u32>) {
+    subdivide(subdivison_level, true)
+}
+
+/// Like [`icosphere`], but without moving the new vertices onto the sphere, so the result is
+/// still a flat-faced icosahedron. Its normals are those of [`compute_normals`], so only the
+/// vertices on the edges of the icosahedron are shaded with an average of its faces.
+///
+/// It has the same topology and vertex order as the icosphere with the same subdivision level,
+/// which makes it possible to interpolate between the two.
+pub fn subdivided_icosahedron(subdivison_level: u8) -> (Vec<Vertex>, Vec<u32>) {
+    subdivide(subdivison_level, false)
+}
+
+/// Splits every triangle of the icosahedron into four, `subdivison_level` times. The new
+/// vertices are moved onto the circumscribed sphere if `spherical` is set.
+fn subdivide(subdivison_level: u8, spherical: bool) -> (Vec<Vertex>, Vec<u32>) {
+    assert!(
+        subdivison_level <= MAX_SUBDIVISION_LEVEL,
+        "subdivision level {} is above the maximum of {}",
+        subdivison_level,
+        MAX_SUBDIVISION_LEVEL
+    );
     let (mut vertices, mut indices) = create_icosahedron();
 
     let radius = vertices[0].magnitude();
@@ -34,7 +99,10 @@ pub fn icosphere(subdivison_level: u8) -> (Vec<Vertex>, Vec<u32>) {
             None => {
                 let p0 = &vertices[p0_idx as usize];
                 let p1 = &vertices[p1_idx as usize];
-                let mid = (p0 + 0.5 * (p1 - p0)).normalize_to(radius);
+                let mut mid = p0 + 0.5 * (p1 - p0);
+                if spherical {
+                    mid = mid.normalize_to(radius);
+                }
 
                 let index = vertices.len();
                 vertices.push(mid);
@@ -86,69 +154,145 @@ pub fn icosphere(subdivison_level: u8) -> (Vec<Vertex>, Vec<u32>) {
     debug_assert_eq!(final_vertices, vertices.len());
     debug_assert_eq!(final_indices, indices.len());
 
-    (vertices.into_iter().map(|v| v.into()).collect(), indices)
+    // The icosphere is centered at the origin. The texture coordinates are only assigned once the
+    // topology is final, so that seam vertices could be duplicated here without affecting the
+    // subdivision.
+    let mut vertices: Vec<Vertex> = vertices
+        .into_iter()
+        .map(|v| {
+            let direction = v.normalize();
+            Vertex::new(v.into(), direction.into(), spherical_uv(direction))
+        })
+        .collect();
+    // The normal is the direction from the center only on the sphere; the flat faces get theirs
+    // from the triangles
+    if !spherical {
+        compute_normals(&mut vertices, &indices);
+    }
+    (vertices, indices)
+}
+
+/// Maps a unit vector to texture coordinates with an equirectangular projection: `u` is the
+/// longitude around the y axis and `v` the latitude, from 0 at the top to 1 at the bottom.
+///
+/// The mapping has two known artifacts on meshes whose vertices are shared between triangles:
+/// - Triangles that cross the seam at `u` = 0/1 (at negative x) interpolate `u` back across the
+///   whole texture. Fixing this requires duplicating the vertices on the seam with `u` + 1.
+/// - At the poles `u` is undefined, so the triangles touching them get a distorted mapping.
+///   Fixing this requires a separate pole vertex for each of them with the `u` of the triangle.
+fn spherical_uv(direction: Vector3<f32>) -> [f32; 2] {
+    use std::f32::consts::PI;
+    let u = 0.5 + direction.z.atan2(direction.x) / (2.0 * PI);
+    // Rounding can make the magnitude slightly larger than 1
+    let v = 0.5 - direction.y.max(-1.0).min(1.0).asin() / PI;
+    [u, v]
 }
 
-// TODO: replace with static or const
+/// The icosahedron as the starting point of the subdivision.
 fn create_icosahedron() -> (Vec<Vector3<f32>>, Vec<u32>) {
-    let tau = (1. + 5f32.sqrt()) / 2.;
-
-    let vertices = vec![
-        Vector3::new(1., tau, 0.), // p0
-        Vector3::new(1., -tau, 0.),
-        Vector3::new(-1., -tau, 0.), // p2
-        Vector3::new(-1., tau, 0.),
-        //
-        Vector3::new(0., 1., tau),
-        Vector3::new(0., 1., -tau),
-        Vector3::new(0., -1., -tau),
-        Vector3::new(0., -1., tau),
-        //
-        Vector3::new(tau, 0., 1.),
-        Vector3::new(-tau, 0., 1.),
-        Vector3::new(-tau, 0., -1.),
-        Vector3::new(tau, 0., -1.),
-    ];
-    let mut indices = Vec::new();
-    // TODO: replace with static values
-    for i in 0..12 {
-        for j in 0..12 {
-            for k in 0..12 {
-                if !(i < j && j < k) {
-                    continue;
-                }
-                let a = vertices[j] - vertices[i];
-                let b = vertices[k] - vertices[i];
-                let c = vertices[k] - vertices[j];
-                let a_magn = a.magnitude();
-                let b_magn = b.magnitude();
-                let c_magn = c.magnitude();
-                if (2. - a_magn).abs() < 0.1
-                    && (2. - b_magn).abs() < 0.1
-                    && (2. - c_magn).abs() < 0.1
-                {
-                    let cross = a.cross(b);
-                    let expected = vertices[i] + vertices[j] + vertices[k];
-                    // TODO: Redo calculations; My last result was that these two branches should be swapped...
-                    if (cross.x == 0. && expected.x == 0.
-                        || cross.x.is_sign_positive() == expected.x.is_sign_positive())
-                        && (cross.y == 0. && expected.y == 0.
-                            || cross.y.is_sign_positive() == expected.y.is_sign_positive())
-                        && (cross.z == 0. && expected.z == 0.
-                            || cross.z.is_sign_positive() == expected.z.is_sign_positive())
-                    {
-                        indices.push(i as u32);
-                        indices.push(k as u32);
-                        indices.push(j as u32);
-                    } else {
-                        indices.push(i as u32);
-                        indices.push(j as u32);
-                        indices.push(k as u32);
-                    }
-                }
+    let vertices: Vec<Vector3<f32>> = ICOSAHEDRON_VERTICES.iter().map(|&v| v.into()).collect();
+    (vertices, ICOSAHEDRON_INDICES.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_match_eulers_formula() {
+        for level in 0..=5 {
+            let (vertices, indices) = icosphere(level);
+            let faces = 20 * 4usize.pow(level.into());
+            // Every shared edge midpoint is only created once; V = 2 + E - F with E = 3F / 2
+            assert_eq!(vertices.len(), 2 + faces / 2, "level {}", level);
+            assert_eq!(indices.len(), 3 * faces, "level {}", level);
+        }
+    }
+
+    #[test]
+    fn indices_are_in_bounds() {
+        for level in 0..=5 {
+            let (vertices, indices) = icosphere(level);
+            assert!(indices.iter().all(|&i| (i as usize) < vertices.len()));
+        }
+    }
+
+    #[test]
+    fn vertices_lie_on_the_sphere() {
+        let radius = Vector3::from(ICOSAHEDRON_VERTICES[0]).magnitude();
+        for level in 0..=5 {
+            let (vertices, _) = icosphere(level);
+            for vertex in &vertices {
+                let distance = Vector3::from(vertex.position()).magnitude();
+                assert!(
+                    (distance - radius).abs() <= 1e-5 * radius,
+                    "level {}",
+                    level
+                );
             }
         }
     }
 
-    (vertices, indices)
+    #[test]
+    fn triangles_are_not_degenerate() {
+        for level in 0..=5 {
+            let (vertices, indices) = icosphere(level);
+            for face in indices.chunks(3) {
+                assert!(face[0] != face[1] && face[1] != face[2] && face[2] != face[0]);
+                let position = |i: u32| Vector3::from(vertices[i as usize].position());
+                let (a, b, c) = (position(face[0]), position(face[1]), position(face[2]));
+                assert!((b - a).cross(c - a).magnitude2() > 0.0, "level {}", level);
+            }
+        }
+    }
+
+    /// Counter-clockwise from the outside means that (b - a) × (c - a) points away from the
+    /// center.
+    fn faces_outwards(vertices: &[Vector3<f32>], face: &[u32]) -> bool {
+        let (a, b, c) = (
+            vertices[face[0] as usize],
+            vertices[face[1] as usize],
+            vertices[face[2] as usize],
+        );
+        (b - a).cross(c - a).dot(a + b + c) > 0.0
+    }
+
+    #[test]
+    fn triangles_wind_outwards() {
+        for level in 0..=4 {
+            let (vertices, indices) = icosphere(level);
+            let positions: Vec<Vector3<f32>> =
+                vertices.iter().map(|v| v.position().into()).collect();
+            for face in indices.chunks(3) {
+                assert!(faces_outwards(&positions, face), "level {}", level);
+            }
+        }
+    }
+
+    #[test]
+    fn create_icosahedron_returns_the_tables() {
+        let (vertices, indices) = create_icosahedron();
+        let vertices: Vec<[f32; 3]> = vertices.into_iter().map(Into::into).collect();
+        assert_eq!(vertices, ICOSAHEDRON_VERTICES.to_vec());
+        assert_eq!(indices, ICOSAHEDRON_INDICES.to_vec());
+    }
+
+    #[test]
+    fn icosahedron_tables_form_an_icosahedron() {
+        let vertices: Vec<Vector3<f32>> = ICOSAHEDRON_VERTICES.iter().map(|&v| v.into()).collect();
+        let mut edges = HashMap::new();
+        for face in ICOSAHEDRON_INDICES.chunks(3) {
+            assert!(faces_outwards(&vertices, face));
+            for k in 0..3 {
+                let (a, b) = (face[k], face[(k + 1) % 3]);
+                *edges.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+        assert_eq!(edges.len(), 30);
+        for (&(a, b), &count) in &edges {
+            assert_eq!(count, 2, "edge {}-{}", a, b);
+            let length = (vertices[a as usize] - vertices[b as usize]).magnitude();
+            assert!((length - 2.0).abs() < 1e-5, "edge {}-{}", a, b);
+        }
+    }
 }
@@ -0,0 +1,249 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use cgmath::{InnerSpace, Matrix, Matrix4, Point3, SquareMatrix, Transform, Vector3};
+use gltf::mesh::Mode;
+
+use crate::mesh::{compute_normals, faces_inward, flip_winding, Mesh};
+use crate::vertex::Vertex;
+
+/// Imports the first primitive of the first mesh in a `.gltf` (with its buffers next to it) or
+/// `.glb` file.
+///
+/// The positions and normals are transformed into world space by the transforms of the node the
+/// mesh is attached to and its parents. Primitives without normals get them computed with
+/// [`compute_normals`]. The first set of texture coordinates is used if there is one, otherwise
+/// they're all zero. Unindexed primitives get sequential indices.
+///
+/// glTF triangles are counter-clockwise when seen from the outside, which is the winding of the
+/// [`icosphere`](crate::icosphere::icosphere) as well, but some exporters get it wrong. `winding`
+/// decides whether the triangles are flipped; the second value returned is whether they were.
+pub fn import_gltf(path: &Path, winding: Winding) -> Result<(Mesh, bool), String> {
+    let (document, buffers, _) = gltf::import(path).map_err(|e| e.to_string())?;
+
+    let (mesh, transform) = first_mesh(&document)?;
+    let primitive = first_primitive(&mesh)?;
+
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let mut vertices: Vec<Vertex> = reader
+        .read_positions()
+        .ok_or_else(|| "the primitive has no positions".to_string())?
+        .map(|p| Vertex::from_position(transform.transform_point(Point3::from(p)).into()))
+        .collect();
+    if vertices.is_empty() {
+        return Err("the primitive has no vertices".to_string());
+    }
+    let normals = reader.read_normals();
+    if let Some(normals) = &normals {
+        if normals.len() != vertices.len() {
+            return Err(format!(
+                "{} normals don't match {} positions",
+                normals.len(),
+                vertices.len()
+            ));
+        }
+    }
+    if let Some(uvs) = reader.read_tex_coords(0) {
+        let uvs = uvs.into_f32();
+        if uvs.len() != vertices.len() {
+            return Err(format!(
+                "{} texture coordinates don't match {} positions",
+                uvs.len(),
+                vertices.len()
+            ));
+        }
+        for (vertex, uv) in vertices.iter_mut().zip(uvs) {
+            vertex.set_uv(uv);
+        }
+    }
+    let mut indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertices.len() as u32).collect(),
+    };
+    if indices.len() % 3 != 0 {
+        return Err(format!(
+            "{} indices don't form whole triangles",
+            indices.len()
+        ));
+    }
+    if let Some(&index) = indices.iter().find(|&&i| i as usize >= vertices.len()) {
+        return Err(format!(
+            "index {} is out of range for {} vertices",
+            index,
+            vertices.len()
+        ));
+    }
+    // Before the normals are computed from the triangles
+    let flip = match winding {
+        Winding::Keep => false,
+        Winding::Flip => true,
+        Winding::Detect => faces_inward(&vertices, &indices),
+    };
+    if flip {
+        flip_winding(&mut indices);
+    }
+    match normals {
+        Some(normals) => {
+            // Keeps the normals perpendicular to the surface under non-uniform scaling
+            let normal_matrix = transform
+                .invert()
+                .ok_or_else(|| "the node transform is singular".to_string())?
+                .transpose();
+            for (vertex, normal) in vertices.iter_mut().zip(normals) {
+                let normal = normal_matrix.transform_vector(Vector3::from(normal));
+                vertex.set_normal(normal.normalize().into());
+            }
+        }
+        None => compute_normals(&mut vertices, &indices),
+    }
+
+    Ok((Mesh::new(vertices, indices), flip))
+}
+
+/// How [`import_gltf`] treats the winding of the triangles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Winding {
+    /// Keeps the winding of the file.
+    Keep,
+    /// Flips every triangle, for files with clockwise triangles.
+    Flip,
+    /// Flips every triangle if most of them face inwards according to [`faces_inward`].
+    Detect,
+}
+
+/// Reads the positions and indices of a Wavefront OBJ file, or of the primitive that
+/// [`import_gltf`] would import from a glTF file, as they are stored. Unlike the import, this
+/// doesn't check the indices, e.g. to report every problem with
+/// [`validate`](crate::validate::validate). OBJ indices before the first vertex become
+/// `u32::MAX`, and polygons with more than three vertices are split into triangle fans.
+pub fn read_triangles(path: &Path) -> Result<(Vec<Vertex>, Vec<u32>), String> {
+    let is_obj = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map_or(false, |extension| extension.eq_ignore_ascii_case("obj"));
+    if is_obj {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        return read_obj(BufReader::new(file));
+    }
+
+    let (document, buffers, _) = gltf::import(path).map_err(|e| e.to_string())?;
+    let (mesh, transform) = first_mesh(&document)?;
+    let primitive = first_primitive(&mesh)?;
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let vertices: Vec<Vertex> = reader
+        .read_positions()
+        .ok_or_else(|| "the primitive has no positions".to_string())?
+        .map(|p| Vertex::from_position(transform.transform_point(Point3::from(p)).into()))
+        .collect();
+    let indices = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertices.len() as u32).collect(),
+    };
+    Ok((vertices, indices))
+}
+
+/// Reads the `v` and `f` lines of an OBJ file; everything else is ignored.
+fn read_obj(reader: impl BufRead) -> Result<(Vec<Vertex>, Vec<u32>), String> {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let error = |what: &str| format!("line {}: {}", number + 1, what);
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => {
+                let mut position = [0.0; 3];
+                for coord in &mut position {
+                    *coord = words
+                        .next()
+                        .and_then(|word| word.parse().ok())
+                        .ok_or_else(|| error("expected three coordinates"))?;
+                }
+                vertices.push(Vertex::from_position(position));
+            }
+            Some("f") => {
+                // The position index is the first of the `v/vt/vn` indices
+                let face = words
+                    .map(|word| {
+                        let index: i64 = word
+                            .split('/')
+                            .next()
+                            .unwrap()
+                            .parse()
+                            .map_err(|_| error("expected vertex indices"))?;
+                        // Negative indices count back from the last vertex
+                        let index = if index < 0 {
+                            vertices.len() as i64 + index
+                        } else {
+                            index - 1
+                        };
+                        Ok(if index >= 0 && index < i64::from(u32::max_value()) {
+                            index as u32
+                        } else {
+                            u32::max_value()
+                        })
+                    })
+                    .collect::<Result<Vec<u32>, String>>()?;
+                if face.len() < 3 {
+                    return Err(error("a face needs at least three vertices"));
+                }
+                for i in 1..face.len() - 1 {
+                    indices.extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok((vertices, indices))
+}
+
+/// The first mesh in the default scene, or in the file if it has no scenes, and the transform of
+/// the node it's attached to.
+fn first_mesh(document: &gltf::Document) -> Result<(gltf::Mesh<'_>, Matrix4<f32>), String> {
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next());
+    let (mesh_index, transform) = scene
+        .and_then(|scene| {
+            scene
+                .nodes()
+                .filter_map(|node| find_mesh(&node, Matrix4::identity()))
+                .next()
+        })
+        // Files without scenes can still contain meshes
+        .unwrap_or((0, Matrix4::identity()));
+    let mesh = document
+        .meshes()
+        .nth(mesh_index)
+        .ok_or_else(|| "the file contains no meshes".to_string())?;
+    Ok((mesh, transform))
+}
+
+/// The first primitive of `mesh`, which has to be a triangle list.
+fn first_primitive<'a>(mesh: &'a gltf::Mesh<'a>) -> Result<gltf::Primitive<'a>, String> {
+    let primitive = mesh
+        .primitives()
+        .next()
+        .ok_or_else(|| "the mesh has no primitives".to_string())?;
+    if primitive.mode() != Mode::Triangles {
+        return Err(format!(
+            "unsupported primitive mode {:?}; only triangle lists are supported",
+            primitive.mode()
+        ));
+    }
+    Ok(primitive)
+}
+
+/// Depth-first search for the first node with a mesh below `node`, whose parent transform is
+/// `parent`. Returns the index of the mesh and its accumulated transform.
+fn find_mesh(node: &gltf::Node, parent: Matrix4<f32>) -> Option<(usize, Matrix4<f32>)> {
+    let transform = parent * Matrix4::from(node.transform().matrix());
+    match node.mesh() {
+        Some(mesh) => Some((mesh.index(), transform)),
+        None => node
+            .children()
+            .filter_map(|child| find_mesh(&child, transform))
+            .next(),
+    }
+}
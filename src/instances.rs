@@ -0,0 +1,148 @@
+#![allow(clippy::ref_in_deref)]
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+use vulkano::impl_vertex;
+
+/// Per-instance vertex data of the mesh pipelines, which draw a copy of the mesh for each
+/// instance.
+#[derive(Debug, Clone)]
+pub struct InstanceData {
+    translation: [f32; 3],
+    /// Multiplied with the base color of the mesh.
+    color: [f32; 3],
+}
+impl_vertex!(InstanceData, translation, color);
+
+impl InstanceData {
+    pub fn new(translation: [f32; 3], color: [f32; 3]) -> InstanceData {
+        InstanceData { translation, color }
+    }
+
+    pub fn translation(&self) -> Vector3<f32> {
+        self.translation.into()
+    }
+
+    /// Distance from `pos` to the instance.
+    pub fn distance(&self, pos: Point3<f32>) -> f32 {
+        (Point3::from_vec(self.translation()) - pos).magnitude()
+    }
+}
+
+/// A corner of the quad that an impostor is drawn on, in units of its size.
+#[derive(Debug, Clone)]
+pub struct ImpostorCorner {
+    corner: [f32; 2],
+}
+impl_vertex!(ImpostorCorner, corner);
+
+/// The quad as a triangle strip.
+pub const IMPOSTOR_CORNERS: [ImpostorCorner; 4] = [
+    ImpostorCorner {
+        corner: [-1.0, -1.0],
+    },
+    ImpostorCorner {
+        corner: [1.0, -1.0],
+    },
+    ImpostorCorner {
+        corner: [-1.0, 1.0],
+    },
+    ImpostorCorner { corner: [1.0, 1.0] },
+];
+
+/// How the instances of a grid are tinted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstanceColors {
+    /// Every instance keeps the color of the mesh.
+    White,
+    /// The red, green and blue channels grow along the x, y and z axes of the grid.
+    Position,
+    /// Random colors that are the same for the same seed.
+    Random(u64),
+}
+
+/// A cube of `n`×`n`×`n` instances centered at the origin, `spacing` apart from each other.
+pub fn grid(n: u32, spacing: f32, colors: InstanceColors) -> Vec<InstanceData> {
+    let offset = |i: u32| (i as f32 - (n - 1) as f32 / 2.0) * spacing;
+    // 0 to 1 from one side of the grid to the other, or 1 if there is only one instance
+    let fraction = |i: u32| {
+        if n > 1 {
+            i as f32 / (n - 1) as f32
+        } else {
+            1.0
+        }
+    };
+    let mut random = match colors {
+        // Xorshift is stuck at 0, which is a likely seed
+        InstanceColors::Random(seed) => Xorshift((seed ^ 0x9e37_79b9_7f4a_7c15).max(1)),
+        _ => Xorshift(1),
+    };
+    let mut instances = Vec::with_capacity((n * n * n) as usize);
+    for x in 0..n {
+        for y in 0..n {
+            for z in 0..n {
+                let color = match colors {
+                    InstanceColors::White => [1.0; 3],
+                    InstanceColors::Position => [fraction(x), fraction(y), fraction(z)],
+                    // Not too dark to see the shading
+                    InstanceColors::Random(_) => {
+                        let mut channel = || 0.25 + 0.75 * random.next_f32();
+                        [channel(), channel(), channel()]
+                    }
+                };
+                instances.push(InstanceData::new([offset(x), offset(y), offset(z)], color));
+            }
+        }
+    }
+    instances
+}
+
+/// A xorshift64 pseudo-random number generator, which is good enough for colors.
+struct Xorshift(u64);
+
+impl Xorshift {
+    /// A number between 0 and 1.
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        // The upper 24 bits fit into the mantissa
+        (self.0 >> 40) as f32 / (1 << 24) as f32
+    }
+}
+
+/// Distance from `pos` to the closest instance, or infinity if there are none.
+pub fn nearest_distance(instances: &[InstanceData], pos: Point3<f32>) -> f32 {
+    instances
+        .iter()
+        .map(|instance| instance.distance(pos))
+        .fold(std::f32::INFINITY, f32::min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_are_assigned_per_instance() {
+        let white = grid(2, 1.0, InstanceColors::White);
+        assert!(white.iter().all(|instance| instance.color == [1.0; 3]));
+
+        let position = grid(2, 1.0, InstanceColors::Position);
+        assert_eq!(position[0].color, [0.0, 0.0, 0.0]);
+        assert_eq!(position[7].color, [1.0, 1.0, 1.0]);
+        assert_eq!(grid(1, 1.0, InstanceColors::Position)[0].color, [1.0; 3]);
+
+        let colors = |seed| {
+            grid(3, 1.0, InstanceColors::Random(seed))
+                .iter()
+                .map(|instance| instance.color)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(colors(7), colors(7));
+        assert_ne!(colors(7), colors(8));
+        assert!(colors(7)
+            .iter()
+            .flatten()
+            .all(|channel| (0.25..=1.0).contains(channel)));
+    }
+}
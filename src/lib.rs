@@ -0,0 +1,17 @@
+//! Geometry generation for CurveBall.
+//!
+//! The renderer in `main.rs` is built on top of these modules, but they don't depend on any
+//! Vulkan state and can be used by other applications to generate meshes for their own renderers.
+
+pub mod adjacency;
+pub mod bounds;
+pub mod explode;
+pub mod export;
+pub mod icosphere;
+pub mod import;
+pub mod mesh;
+pub mod mesh_opt;
+pub mod png;
+pub mod smooth;
+pub mod validate;
+pub mod vertex;
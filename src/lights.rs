@@ -0,0 +1,164 @@
+use cgmath::{Angle, Deg, EuclideanSpace, InnerSpace, Point3, Rad, Vector3};
+
+use crate::shaders;
+
+/// Maximum number of lights; must match `MAX_LIGHTS` in the fragment shaders.
+pub const MAX_LIGHTS: usize = 8;
+
+/// Maximum elevation of the primary light (89°), which keeps its azimuth well-defined.
+const MAX_ELEVATION: Rad<f32> = Rad(1.553_343);
+
+/// Colors cycled through by lights added at runtime.
+const COLORS: [[f32; 3]; 4] = [
+    [1.0, 1.0, 1.0],
+    [0.2, 0.4, 1.0],
+    [0.2, 1.0, 0.4],
+    [1.0, 0.8, 0.2],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    /// Infinitely far away light; `position` is the direction towards it.
+    Directional,
+    /// Light at `position`, shining in all directions.
+    Point,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Light {
+    pub kind: LightKind,
+    /// World space position of a point light or direction towards a directional light.
+    pub position: Vector3<f32>,
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+impl Light {
+    pub fn directional(direction: Vector3<f32>, color: [f32; 3], intensity: f32) -> Light {
+        Light {
+            kind: LightKind::Directional,
+            position: direction,
+            color,
+            intensity,
+        }
+    }
+
+    pub fn point(position: Point3<f32>, color: [f32; 3], intensity: f32) -> Light {
+        Light {
+            kind: LightKind::Point,
+            position: position.to_vec(),
+            color,
+            intensity,
+        }
+    }
+}
+
+/// The lights of the scene, uploaded to the `Lights` uniform every frame.
+pub struct Lights {
+    lights: Vec<Light>,
+}
+
+impl Default for Lights {
+    /// A single white directional light, which looks like the old hard-coded light.
+    fn default() -> Lights {
+        Lights {
+            lights: vec![Light::directional(
+                Vector3::new(4.0, 4.0, 4.0),
+                COLORS[0],
+                1.0,
+            )],
+        }
+    }
+}
+
+impl Lights {
+    pub fn num_lights(&self) -> usize {
+        self.lights.len()
+    }
+
+    /// Adds a point light at `position` unless there are already `MAX_LIGHTS` lights. Returns
+    /// whether the light was added.
+    pub fn add_point(&mut self, position: Point3<f32>) -> bool {
+        if self.lights.len() >= MAX_LIGHTS {
+            return false;
+        }
+        let color = COLORS[self.lights.len() % COLORS.len()];
+        self.lights.push(Light::point(position, color, 1.0));
+        true
+    }
+
+    /// Removes the most recently added light.
+    pub fn remove_last(&mut self) -> Option<Light> {
+        self.lights.pop()
+    }
+
+    /// Moves the most recently added point light to `position`. Returns whether there was one.
+    pub fn move_last(&mut self, position: Point3<f32>) -> bool {
+        match self.lights.last_mut() {
+            Some(light) if light.kind == LightKind::Point => {
+                light.position = position.to_vec();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The azimuth around the y axis (starting at the x axis) and the elevation of the direction
+    /// towards the first directional light, or `None` if there is no directional light.
+    pub fn primary_angles(&self) -> Option<(Deg<f32>, Deg<f32>)> {
+        let light = self
+            .lights
+            .iter()
+            .find(|light| light.kind == LightKind::Directional)?;
+        let direction = light.position.normalize();
+        Some((
+            Rad::atan2(direction.z, direction.x).normalize().into(),
+            Rad::asin(direction.y).into(),
+        ))
+    }
+
+    /// Rotates the direction towards the first directional light around the y axis by `azimuth`
+    /// and up or down by `elevation`, which is limited to just below the poles.
+    pub fn rotate_primary(&mut self, azimuth: Rad<f32>, elevation: Rad<f32>) {
+        let (old_azimuth, old_elevation) = match self.primary_angles() {
+            Some(angles) => angles,
+            None => return,
+        };
+        let azimuth = Rad::from(old_azimuth) + azimuth;
+        let elevation = (Rad::from(old_elevation) + elevation)
+            .0
+            .max(-MAX_ELEVATION.0)
+            .min(MAX_ELEVATION.0);
+        let elevation = Rad(elevation);
+        let light = self
+            .lights
+            .iter_mut()
+            .find(|light| light.kind == LightKind::Directional)
+            .unwrap();
+        light.position = Vector3::new(
+            elevation.cos() * azimuth.cos(),
+            elevation.sin(),
+            elevation.cos() * azimuth.sin(),
+        );
+    }
+
+    /// The uniform data for the fragment shaders. The impostor shader declares the same block.
+    pub fn uniform(&self) -> shaders::fs::ty::Lights {
+        let mut data = shaders::fs::ty::Lights {
+            lights: [shaders::fs::ty::Light {
+                position: [0.0; 4],
+                color: [0.0; 4],
+            }; MAX_LIGHTS],
+            num_lights: self.lights.len() as u32,
+        };
+        for (light, data) in self.lights.iter().zip(data.lights.iter_mut()) {
+            let w = match light.kind {
+                LightKind::Directional => 0.0,
+                LightKind::Point => 1.0,
+            };
+            data.position = light.position.extend(w).into();
+            data.color = Vector3::from(light.color).extend(light.intensity).into();
+        }
+        data
+    }
+}
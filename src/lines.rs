@@ -0,0 +1,95 @@
+#![allow(clippy::ref_in_deref)]
+
+use vulkano::impl_vertex;
+
+use curveball::bounds::Aabb;
+use curveball::mesh::triangle_areas;
+use curveball::vertex::Vertex;
+
+/// A vertex of the debug line pipeline, which is also used for the colored triangles of the
+/// heat map pipeline.
+#[derive(Debug, Clone)]
+pub struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+impl_vertex!(LineVertex, position, color);
+
+impl LineVertex {
+    pub fn new(position: [f32; 3], color: [f32; 3]) -> LineVertex {
+        LineVertex { position, color }
+    }
+}
+
+/// What the heat map pipeline colors the triangles by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Heatmap {
+    /// See [`area_heatmap`].
+    Area,
+    /// See [`index_order_heatmap`]; the order before or after the vertex cache optimization.
+    IndexOrder { optimized: bool },
+}
+
+/// Line list vertices for the 12 edges of `aabb`.
+pub fn aabb_lines(aabb: &Aabb, color: [f32; 3]) -> Vec<LineVertex> {
+    aabb.edges()
+        .iter()
+        .flat_map(|&(start, end)| {
+            vec![
+                LineVertex::new(start.into(), color),
+                LineVertex::new(end.into(), color),
+            ]
+        })
+        .collect()
+}
+
+/// Triangle list vertices that color every triangle by its area: triangles with the mean area are
+/// white and they get bluer the smaller and redder the larger they are.
+pub fn area_heatmap(vertices: &[Vertex], indices: &[u32]) -> Vec<LineVertex> {
+    let areas = triangle_areas(vertices, indices);
+    let mean = areas.iter().sum::<f32>() / areas.len().max(1) as f32;
+    // The largest deviation maps to pure blue or red
+    let max_deviation = areas
+        .iter()
+        .map(|area| (area - mean).abs())
+        .fold(0.0, f32::max)
+        .max(std::f32::EPSILON);
+
+    indices
+        .chunks(3)
+        .zip(areas)
+        .flat_map(|(face, area)| {
+            let t = (area - mean) / max_deviation;
+            let color = if t < 0.0 {
+                [1.0 + t, 1.0 + t, 1.0]
+            } else {
+                [1.0, 1.0 - t, 1.0 - t]
+            };
+            face.iter()
+                .map(|&i| LineVertex::new(vertices[i as usize].position(), color))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Triangle list vertices that color every triangle by its position in the index buffer, from
+/// blue for the first one over green to red for the last one. The fewer sudden color changes
+/// there are between neighboring triangles, the better the vertex cache is used.
+pub fn index_order_heatmap(vertices: &[Vertex], indices: &[u32]) -> Vec<LineVertex> {
+    let last = (indices.len() / 3).max(2) - 1;
+    indices
+        .chunks(3)
+        .enumerate()
+        .flat_map(|(triangle, face)| {
+            let t = triangle as f32 / last as f32;
+            let color = if t < 0.5 {
+                [0.0, 2.0 * t, 1.0 - 2.0 * t]
+            } else {
+                [2.0 * t - 1.0, 2.0 - 2.0 * t, 0.0]
+            };
+            face.iter()
+                .map(|&i| LineVertex::new(vertices[i as usize].position(), color))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
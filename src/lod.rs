@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use vulkano::buffer::{BufferAccess, BufferUsage, ImmutableBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DrawIndexedError, DynamicState};
+use vulkano::descriptor::descriptor_set::DescriptorSetsCollection;
+use vulkano::device::Queue;
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::pipeline::vertex::VertexSource;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::sync::GpuFuture;
+
+use curveball::mesh::Indices;
+use curveball::vertex::Vertex;
+
+/// Relative distance by which the camera has to move past a switching distance before another
+/// level is selected; avoids flickering between two levels around the switching distance.
+const HYSTERESIS: f32 = 0.1;
+
+/// An index buffer with either 16- or 32-bit indices, see [`Indices`].
+pub enum IndexBuffer {
+    U16(Arc<ImmutableBuffer<[u16]>>),
+    U32(Arc<ImmutableBuffer<[u32]>>),
+}
+
+impl IndexBuffer {
+    /// Uploads `indices` with `queue`, see [`Lod::new`].
+    pub fn new(
+        queue: Arc<Queue>,
+        indices: Indices,
+    ) -> Result<(IndexBuffer, Box<dyn GpuFuture>), DeviceMemoryAllocError> {
+        let usage = BufferUsage::index_buffer();
+        Ok(match indices {
+            Indices::U16(indices) => {
+                let (buffer, future) =
+                    ImmutableBuffer::from_iter(indices.into_iter(), usage, queue)?;
+                (IndexBuffer::U16(buffer), Box::new(future) as Box<_>)
+            }
+            Indices::U32(indices) => {
+                let (buffer, future) =
+                    ImmutableBuffer::from_iter(indices.into_iter(), usage, queue)?;
+                (IndexBuffer::U32(buffer), Box::new(future) as Box<_>)
+            }
+        })
+    }
+
+    /// Records an indexed draw of the first `count` indices.
+    pub fn draw<V, Gp, S>(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        pipeline: Gp,
+        dynamic: &DynamicState,
+        vertex_buffer: V,
+        count: usize,
+        sets: S,
+    ) -> Result<AutoCommandBufferBuilder, DrawIndexedError>
+    where
+        Gp: GraphicsPipelineAbstract + VertexSource<V> + Send + Sync + 'static + Clone,
+        S: DescriptorSetsCollection,
+    {
+        match self {
+            IndexBuffer::U16(buffer) => builder.draw_indexed(
+                pipeline,
+                dynamic,
+                vertex_buffer,
+                buffer.clone().into_buffer_slice().slice(0..count).unwrap(),
+                sets,
+                (),
+            ),
+            IndexBuffer::U32(buffer) => builder.draw_indexed(
+                pipeline,
+                dynamic,
+                vertex_buffer,
+                buffer.clone().into_buffer_slice().slice(0..count).unwrap(),
+                sets,
+                (),
+            ),
+        }
+    }
+}
+
+/// The GPU buffers of one level of detail.
+pub struct Lod {
+    pub vertex_buffer: Arc<ImmutableBuffer<[Vertex]>>,
+    pub index_buffer: IndexBuffer,
+    pub triangles: usize,
+    /// Up to which distance from the camera this level is used
+    pub max_distance: f32,
+}
+
+impl Lod {
+    /// Uploads the mesh into device-local buffers with `queue`. The buffers may only be used
+    /// after the returned future.
+    pub fn new(
+        queue: Arc<Queue>,
+        vertices: &[Vertex],
+        indices: Indices,
+        max_distance: f32,
+    ) -> Result<(Lod, Box<dyn GpuFuture>), DeviceMemoryAllocError> {
+        let triangles = indices.num_indices() / 3;
+        let (vertex_buffer, vertex_upload) = ImmutableBuffer::from_iter(
+            vertices.iter().cloned(),
+            BufferUsage::vertex_buffer(),
+            queue.clone(),
+        )?;
+        let (index_buffer, index_upload) = IndexBuffer::new(queue, indices)?;
+        let lod = Lod {
+            vertex_buffer,
+            index_buffer,
+            triangles,
+            max_distance,
+        };
+        Ok((lod, Box::new(vertex_upload.join(index_upload))))
+    }
+}
+
+/// Levels of detail of the same mesh, ordered from the most to the least detailed one.
+pub struct LodSet {
+    lods: Vec<Lod>,
+    current: usize,
+}
+
+impl LodSet {
+    pub fn new(lods: Vec<Lod>) -> LodSet {
+        assert!(!lods.is_empty(), "a LodSet needs at least one level");
+        LodSet { lods, current: 0 }
+    }
+
+    pub fn num_lods(&self) -> usize {
+        self.lods.len()
+    }
+
+    pub fn lod(&self, index: usize) -> &Lod {
+        &self.lods[index]
+    }
+
+    /// Selects the level to use for an object at `distance` from the camera and returns its
+    /// index.
+    pub fn select_lod(&mut self, distance: f32) -> usize {
+        self.current = self.update_lod(self.current, distance);
+        self.current
+    }
+
+    /// Like [`select_lod`](LodSet::select_lod), for objects whose `current` level is kept track
+    /// of by the caller.
+    pub fn update_lod(&self, mut current: usize, distance: f32) -> usize {
+        while current + 1 < self.lods.len()
+            && distance > self.lods[current].max_distance * (1. + HYSTERESIS)
+        {
+            current += 1;
+        }
+        while current > 0 && distance < self.lods[current - 1].max_distance * (1. - HYSTERESIS) {
+            current -= 1;
+        }
+        current
+    }
+}
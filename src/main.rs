@@ -1,60 +1,161 @@
 #![feature(nll)]
 
-mod icosphere;
+mod camera;
+mod config;
+mod gpu_normals;
+mod instances;
+mod lights;
+mod lines;
+mod lod;
+mod movement;
+mod options;
 mod shaders;
-mod vertex;
+mod stats;
+mod stress;
+mod texture;
 
+use std::error::Error;
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::BufWriter;
+use std::iter;
 use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use cgmath::{Deg, Euler, Matrix4, Point3, Quaternion, Rad, SquareMatrix, Vector3};
+use cgmath::{
+    Angle, EuclideanSpace, InnerSpace, Matrix3, Matrix4, Point3, Rad, SquareMatrix, Vector3, Zero,
+};
 use log::*;
+use structopt::StructOpt;
 use vulkano::buffer::cpu_pool::CpuBufferPool;
-use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::buffer::{
+    BufferAccess, BufferUsage, CpuAccessibleBuffer, ImmutableBuffer, TypedBufferAccess,
+};
 use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
 use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
-use vulkano::device::{Device, DeviceExtensions};
-use vulkano::framebuffer::{Framebuffer, Subpass};
+use vulkano::device::{Device, DeviceExtensions, Features};
+use vulkano::format::{ClearValue, Format};
+use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, RenderPassAbstract, Subpass};
+use vulkano::image::{AttachmentImage, ImageUsage};
 use vulkano::instance::debug::{DebugCallback, MessageTypes};
-use vulkano::instance::{Instance, PhysicalDevice};
-use vulkano::pipeline::{viewport::Viewport, GraphicsPipeline};
+use vulkano::instance::{Instance, PhysicalDevice, PhysicalDeviceType};
+use vulkano::pipeline::vertex::OneVertexOneInstanceDefinition;
+use vulkano::pipeline::{viewport::Viewport, GraphicsPipeline, GraphicsPipelineAbstract};
+use vulkano::sampler::Sampler;
 use vulkano::swapchain::{
-    self, AcquireError, PresentMode, SurfaceTransform, Swapchain, SwapchainCreationError,
+    self, AcquireError, Capabilities, ColorSpace, CompositeAlpha, PresentMode,
+    SupportedPresentModes, Surface, SurfaceTransform, Swapchain, SwapchainCreationError,
 };
 use vulkano::sync::{now, FlushError, GpuFuture};
 use vulkano::{ordered_passes_renderpass, single_pass_renderpass};
 use vulkano_win::VkSurfaceBuild;
 use winit::{
-    DeviceEvent, ElementState, Event, EventsLoop, KeyboardInput, VirtualKeyCode, WindowBuilder,
-    WindowEvent,
+    DeviceEvent, ElementState, Event, EventsLoop, KeyboardInput, VirtualKeyCode, Window,
+    WindowBuilder, WindowEvent,
 };
 
-use crate::icosphere::icosphere;
+use curveball::adjacency::build_adjacency;
+use curveball::bounds::bounding_box;
+use curveball::explode::explode;
+use curveball::export::write_obj;
+use curveball::icosphere::{icosphere, subdivided_icosahedron};
+use curveball::import::{import_gltf, read_triangles, Winding};
+use curveball::mesh::{compute_normals, mesh_hash, Indices, Mesh};
+use curveball::mesh_opt::{average_cache_miss_ratio, optimize_indices};
+use curveball::png::write_grayscale_png;
+use curveball::smooth::laplacian_smooth;
+use curveball::validate::{validate, Report};
+use curveball::vertex::Vertex;
+
+use crate::camera::Camera;
+use crate::config::Config;
+use crate::gpu_normals::{GpuNormals, Topology};
+use crate::instances::{nearest_distance, ImpostorCorner, InstanceData, IMPOSTOR_CORNERS};
+use crate::lights::Lights;
+use crate::lines::{aabb_lines, area_heatmap, index_order_heatmap, Heatmap, LineVertex};
+use crate::lod::{Lod, LodSet};
+use crate::movement::{walk, Movement};
+use crate::options::Options;
+use crate::stats::{FrameStats, FrameTimes, TimingCsv, TITLE};
+use crate::stress::{Stress, StressStep};
+use crate::texture::{checkerboard, uv_gradient};
 
-// TODO: mesh optimization
-// - http://gfx.cs.princeton.edu/pubs/Sander_2007_%3ETR/tipsy.pdf
-// - https://tomforsyth1000.github.io/papers/fast_vert_cache_opt.html
-// - http://www.martin.st/thesis/
-// - https://github.com/zeux/meshoptimizer
+/// Size of the FIFO vertex cache for which the average cache miss ratio is printed with
+/// `--stats`; typical for current GPUs.
+const ACMR_CACHE_SIZE: usize = 16;
 
 fn main() {
     env_logger::init();
+    if let Err(err) = run() {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+/// Sets everything up and runs the render loop until the window is closed.
+///
+/// Failures during the setup are returned with a message saying what couldn't be created; the
+/// render loop itself still panics.
+fn run() -> Result<(), Box<dyn Error>> {
+    let config = Config::load(&Options::from_args())
+        .map_err(|err| format!("invalid configuration: {}", err))?;
+
+    if let Some(ref path) = config.export {
+        // Same as the most detailed level of detail
+        let (vertices, indices) = icosphere(config.subdivisions);
+        let file = File::create(path)
+            .map_err(|err| format!("failed to create {}: {}", path.display(), err))?;
+        write_obj(&vertices, &indices, BufWriter::new(file))
+            .map_err(|err| format!("failed to write {}: {}", path.display(), err))?;
+        println!(
+            "Exported {} vertices and {} triangles to {}",
+            vertices.len(),
+            indices.len() / 3,
+            path.display()
+        );
+        return Ok(());
+    }
+
+    if let Some(ref path) = config.validate {
+        let (vertices, indices) = read_triangles(path)
+            .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+        let report = validate(&vertices, &indices);
+        print_report(&report);
+        if !report.is_valid() {
+            return Err(format!("{} is not a valid mesh", path.display()).into());
+        }
+        return Ok(());
+    }
+
+    // Set as soon as one of the setup steps below fails; all following steps then use the most
+    // conservative configuration to maximize the chance of starting up on unusual drivers.
+    let mut safe_mode = false;
 
     let debug = true;
-    let instance = {
+    let instance = or_safe_mode("Vulkan instance", &mut safe_mode, |safe| {
         // All the window-drawing functionalities are part of non-core extensions that we need
         // to enable manually. To do so, we ask the `vulkano_win` crate for the list of extensions
         // required to draw to a window.
         let mut extensions = vulkano_win::required_extensions();
-        extensions.ext_debug_report = debug;
-        let layers = if debug {
+        extensions.ext_debug_report = debug && !safe;
+        let layers = if debug && !safe {
             vec!["VK_LAYER_LUNARG_standard_validation"]
         } else {
             Vec::new()
         };
-        Instance::new(None, &extensions, layers).expect("failed to create Vulkan instance")
-    };
+        Instance::new(None, &extensions, layers)
+    })
+    .map_err(|err| {
+        format!(
+            "failed to create Vulkan instance: {}; is a Vulkan driver installed?",
+            err
+        )
+    })?;
+    // The validation layers are disabled in safe mode.
+    let debug = debug && !safe_mode;
 
     // Must be kept alive or the mssages will disappear!
     let debug_callback = if debug {
@@ -86,52 +187,88 @@ fn main() {
                     unreachable!("unknown debug message type")
                 };
             })
-            .unwrap(),
+            .map_err(|err| format!("failed to register the debug callback: {}", err))?,
         )
     } else {
         None
     };
 
-    // We then choose which physical device to use.
-    //
-    // In a real application, there are three things to take into consideration:
-    //
-    // - Some devices may not support some of the optional features that may be required by your
-    //   application. You should filter out the devices that don't support your app.
-    //
-    // - Not all devices can draw to a certain surface. Once you create your window, you have to
-    //   choose a device that is capable of drawing to it.
-    //
-    // - You probably want to leave the choice between the remaining devices to the user.
-    //
-    // For the sake of the example we are just going to use the first device, which should work
-    // most of the time.
-    let physical = PhysicalDevice::enumerate(&instance)
-        .next()
-        .expect("no device available");
-    println!(
-        "Using device: {} (type: {:?})",
-        physical.name(),
-        physical.ty()
-    );
-
     let mut events_loop = EventsLoop::new();
     let surface = WindowBuilder::new()
+        .with_title(TITLE)
+        // Required for the window to actually be see-through with a non-opaque composite alpha
+        .with_transparency(config.alpha.map_or(false, |a| a != CompositeAlpha::Opaque))
         .build_vk_surface(&events_loop, instance.clone())
-        .unwrap();
+        .map_err(|err| format!("failed to create window: {}", err))?;
 
-    surface.window().grab_cursor(true).unwrap();
+    surface
+        .window()
+        .grab_cursor(true)
+        .map_err(|err| format!("failed to grab the cursor: {}", err))?;
     surface.window().hide_cursor(true);
 
-    // In a real-life application, we would probably use at least a graphics queue and a transfers
-    // queue to handle data transfers in parallel. In this example we only use one queue.
+    // We then choose which physical device to use: the one passed with `--gpu`, or otherwise
+    // preferably a discrete GPU that can draw to the window.
+    println!("Available devices:");
+    for device in PhysicalDevice::enumerate(&instance) {
+        println!(
+            "  #{}: {} (type: {:?})",
+            device.index(),
+            device.name(),
+            device.ty()
+        );
+    }
+    let physical = match config.gpu {
+        Some(index) => PhysicalDevice::from_index(&instance, index).ok_or_else(|| {
+            format!(
+                "there is no device #{}; pass one of the indices listed above",
+                index
+            )
+        })?,
+        None => {
+            let can_present = |device: &PhysicalDevice| {
+                device
+                    .queue_families()
+                    .any(|q| q.supports_graphics() && surface.is_supported(q).unwrap_or(false))
+            };
+            PhysicalDevice::enumerate(&instance)
+                .filter(can_present)
+                .find(|device| device.ty() == PhysicalDeviceType::DiscreteGpu)
+                .or_else(|| PhysicalDevice::enumerate(&instance).find(can_present))
+                // Reports the missing queue family below
+                .or_else(|| PhysicalDevice::enumerate(&instance).next())
+                .ok_or("no Vulkan-capable GPU found")?
+        }
+    };
+    println!(
+        "Using device: {} (type: {:?})",
+        physical.name(),
+        physical.ty()
+    );
+
+    // We use a graphics queue for rendering and, if available, a queue of another family for
+    // uploading the meshes in parallel.
     let queue_family = physical
         .queue_families()
         .find(|&q| {
             // We take the first queue_family that supports drawing to our window.
             q.supports_graphics() && surface.is_supported(q).unwrap_or(false)
         })
-        .expect("couldn't find a graphical queue family");
+        .ok_or_else(|| {
+            log_queue_families(physical, &surface);
+            format!(
+                "couldn't find a queue family of {} that supports graphics and can present to the \
+                 window; on multi-GPU systems the window may be on a display driven by another GPU",
+                physical.name()
+            )
+        })?;
+
+    // Dedicated transfer families are usually backed by DMA engines, so they're preferred over
+    // e.g. a compute family
+    let transfer_family = physical
+        .queue_families()
+        .filter(|q| q.id() != queue_family.id() && q.supports_transfers())
+        .min_by_key(|q| (q.supports_graphics(), q.supports_compute()));
 
     let (device, mut queues) = {
         let device_ext = DeviceExtensions {
@@ -139,21 +276,38 @@ fn main() {
             ..DeviceExtensions::none()
         };
 
-        Device::new(
-            physical,
-            physical.supported_features(),
-            &device_ext,
-            [(queue_family, 0.5)].iter().cloned(),
-        )
-        .expect("failed to create device")
+        or_safe_mode("device", &mut safe_mode, |safe| {
+            // Two queue families are less likely to work than one
+            let transfer_family = transfer_family.filter(|_| !safe);
+            // Safe mode doesn't enable any features. The clip plane and the wireframe view, which
+            // need them, are disabled when their features aren't enabled.
+            let features = if safe {
+                Features::none()
+            } else {
+                physical.supported_features().clone()
+            };
+            Device::new(
+                physical,
+                &features,
+                &device_ext,
+                iter::once((queue_family, 0.5)).chain(transfer_family.map(|q| (q, 0.5))),
+            )
+        })
+        .map_err(|err| format!("failed to create device: {}", err))?
     };
 
-    // We only requested one queue.
+    // The queues are returned in the order they were requested in
     let queue = queues.next().unwrap();
+    let transfer_queue = queues.next().unwrap_or_else(|| queue.clone());
+    println!(
+        "Uploading with queue family #{}",
+        transfer_queue.family().id()
+    );
 
     // The dimensions of the surface.
     // This variable needs to be mutable since the viewport can change size.
     let mut dimensions;
+    let alpha;
 
     // Before we can draw on the surface, we have to create what is called a swapchain. Creating
     // a swapchain allocates the color buffers that will contain the image that will ultimately
@@ -161,125 +315,678 @@ fn main() {
     let (mut swapchain, mut images) = {
         let caps = surface
             .capabilities(physical)
-            .expect("failed to get surface capabilities");
+            .map_err(|err| format!("failed to get surface capabilities: {}", err))?;
 
-        dimensions = caps.current_extent.unwrap_or([1024, 768]);
+        dimensions = swapchain_extent(&caps, surface.window());
 
         // Only determines how the alpha value of the final window pixels are interpreted.
         // (opaque vs. transparent window)
-        let alpha = caps.supported_composite_alpha.iter().next().unwrap();
-        let format = caps.supported_formats[0].0;
-
-        println!("Image format: {:?}", format);
-
-        let present_mode = if caps.present_modes.mailbox {
-            PresentMode::Mailbox
+        let supported_alpha = caps.supported_composite_alpha;
+        let default_alpha = if supported_alpha.opaque {
+            CompositeAlpha::Opaque
         } else {
-            PresentMode::Fifo
+            supported_alpha
+                .iter()
+                .next()
+                .ok_or("the surface supports no composite alpha mode")?
+        };
+        alpha = match config.alpha {
+            Some(requested) if supported_alpha.iter().any(|a| a == requested) => requested,
+            Some(requested) => {
+                warn!(
+                    "composite alpha {:?} isn't supported; falling back to {:?}",
+                    requested, default_alpha
+                );
+                default_alpha
+            }
+            None => default_alpha,
         };
+        println!("Composite alpha: {:?}", alpha);
+        // With an sRGB format the linear colors written by the shaders are gamma-encoded when
+        // they're stored. Swapchains are always created with the sRGB non-linear color space.
+        let srgb_non_linear = caps
+            .supported_formats
+            .iter()
+            .filter(|&&(_, color_space)| color_space == ColorSpace::SrgbNonLinear)
+            .map(|&(format, _)| format)
+            .collect::<Vec<_>>();
+        let format = match [Format::B8G8R8A8Srgb, Format::R8G8B8A8Srgb]
+            .iter()
+            .cloned()
+            .find(|format| srgb_non_linear.contains(format))
+        {
+            Some(format) => format,
+            None => {
+                warn!("the surface supports no sRGB format; colors will look too dark");
+                *srgb_non_linear
+                    .first()
+                    .ok_or("the surface supports no format with the sRGB color space")?
+            }
+        };
+
+        println!("Image format: {:?} (color space: SrgbNonLinear)", format);
 
-        Swapchain::new(
-            device.clone(),
-            surface.clone(),
-            caps.min_image_count + 1, // TODO: What is the correct number for triple buffering?
-            format,
-            dimensions,
-            1, // layers; multiple needed for 3D
-            caps.supported_usage_flags,
-            &queue,
-            SurfaceTransform::Identity,
-            alpha,
-            present_mode,
-            true, // clipped
-            None, // old_swapchain
+        or_safe_mode("swapchain", &mut safe_mode, |safe| {
+            // Fifo is the only present mode that is guaranteed to be supported.
+            let (present_mode, num_images, usage) = if safe {
+                (
+                    PresentMode::Fifo,
+                    caps.min_image_count,
+                    ImageUsage {
+                        color_attachment: true,
+                        ..ImageUsage::none()
+                    },
+                )
+            } else {
+                let present_mode = match config.present_mode {
+                    Some(mode) if present_mode_supported(&caps.present_modes, mode) => mode,
+                    Some(mode) => {
+                        warn!(
+                            "present mode {:?} isn't supported by the surface, using Fifo",
+                            mode
+                        );
+                        PresentMode::Fifo
+                    }
+                    None if caps.present_modes.mailbox => PresentMode::Mailbox,
+                    None => PresentMode::Fifo,
+                };
+                // TODO: What is the correct number for triple buffering?
+                (
+                    present_mode,
+                    caps.min_image_count + 1,
+                    caps.supported_usage_flags,
+                )
+            };
+            println!("Present mode: {:?}", present_mode);
+
+            Swapchain::new(
+                device.clone(),
+                surface.clone(),
+                num_images,
+                format,
+                dimensions,
+                1, // layers; multiple needed for 3D
+                usage,
+                &queue,
+                SurfaceTransform::Identity,
+                alpha,
+                present_mode,
+                true, // clipped
+                None, // old_swapchain
+            )
+        })
+        .map_err(|err| format!("failed to create swapchain: {}", err))?
+    };
+
+    // Pairs of subdivision levels and up to which distance they're used
+    let mut lod_levels = vec![
+        (config.subdivisions, 5.0),
+        (config.subdivisions.saturating_sub(2), 15.0),
+        (config.subdivisions.saturating_sub(4), std::f32::INFINITY),
+    ];
+    // Low subdivision levels are used at all the distances of the levels they replace
+    lod_levels.dedup_by(|farther, nearer| {
+        let same = farther.0 == nearer.0;
+        if same {
+            nearer.1 = farther.1;
+        }
+        same
+    });
+    // The meshes of the levels of detail, their subdivision levels and up to which distance
+    // they're used. An imported mesh is used at all distances.
+    let sources = match config.gltf {
+        Some(ref path) => {
+            let winding = if config.flip_winding {
+                Winding::Flip
+            } else {
+                Winding::Detect
+            };
+            let (mesh, flipped) = import_gltf(path, winding)
+                .map_err(|err| format!("failed to import {}: {}", path.display(), err))?;
+            if flipped && !config.flip_winding {
+                warn!(
+                    "most triangles of {} are clockwise; reversed their winding",
+                    path.display()
+                );
+            }
+            println!(
+                "Imported {}: {} vertices, {} triangles",
+                path.display(),
+                mesh.vertices().len(),
+                mesh.indices().len() / 3
+            );
+            vec![(mesh, None, std::f32::INFINITY)]
+        }
+        None => lod_levels
+            .iter()
+            .map(|&(level, max_distance)| (Mesh::from(icosphere(level)), Some(level), max_distance))
+            .collect(),
+    };
+    let mut bounds = None;
+    let mut radius = 0.0;
+    // Kept on the CPU to build the exploded view from
+    let mut meshes = Vec::new();
+    // The triangle order of the levels before the vertex cache optimization, for the heat map
+    let mut unoptimized_indices = Vec::new();
+    // Flat-faced counterparts of the levels with the same topology, which the sphere can morph into
+    let mut morph_targets = Vec::new();
+    // Uploads of the levels of detail, which the first frame waits for
+    let mut uploads = Vec::new();
+    let mut lods = LodSet::new(
+        sources
+            .into_iter()
+            .enumerate()
+            .map(|(i, (mesh, level, max_distance))| {
+                let (mut vertices, mut indices) = mesh.into_parts();
+                let unoptimized_acmr = if config.stats {
+                    Some(average_cache_miss_ratio(&indices, ACMR_CACHE_SIZE))
+                } else {
+                    None
+                };
+                unoptimized_indices.push(indices.clone());
+                optimize_indices(&mut indices, vertices.len());
+                if config.smooth > 0 {
+                    let adjacency = build_adjacency(&indices, vertices.len());
+                    laplacian_smooth(&mut vertices, &adjacency, config.smooth, 0.5);
+                    compute_normals(&mut vertices, &indices);
+                }
+                if let Some(unoptimized_acmr) = unoptimized_acmr {
+                    println!(
+                        "Mesh {}: {} vertices, {} triangles, hash {:016x}, ACMR {:.3} (was {:.3})",
+                        i,
+                        vertices.len(),
+                        indices.len() / 3,
+                        mesh_hash(&vertices, &indices),
+                        average_cache_miss_ratio(&indices, ACMR_CACHE_SIZE),
+                        unoptimized_acmr
+                    );
+                }
+                // The most detailed level approximates the sphere best
+                if bounds.is_none() {
+                    bounds = bounding_box(&vertices);
+                    if let Some(vertex) = vertices.first() {
+                        radius = Vector3::from(vertex.position()).magnitude();
+                    }
+                }
+                let (lod, upload) = Lod::new(
+                    transfer_queue.clone(),
+                    &vertices,
+                    Indices::compact(indices.clone(), vertices.len()),
+                    max_distance,
+                )
+                .map_err(|err| format!("failed to create mesh buffers: {}", err))?;
+                uploads.push(upload);
+                // Imported meshes have nothing to morph into
+                morph_targets.push(match level {
+                    Some(level) => subdivided_icosahedron(level).0,
+                    None => vertices.clone(),
+                });
+                meshes.push(Mesh::new(vertices, indices));
+                Ok(lod)
+            })
+            .collect::<Result<_, String>>()?,
+    );
+
+    // With --gpu-normals the normals of the morph are computed in a compute shader, which needs
+    // the triangles of every level in storage buffers
+    let gpu_normals = if !config.gpu_normals {
+        None
+    } else if !queue.family().supports_compute() {
+        warn!("the graphics queue doesn't support compute; computing the normals on the CPU");
+        None
+    } else {
+        let topologies = meshes
+            .iter()
+            .map(|mesh| {
+                let (topology, upload) = Topology::new(
+                    transfer_queue.clone(),
+                    mesh.indices(),
+                    mesh.vertices().len(),
+                )
+                .map_err(|err| format!("failed to create topology buffers: {}", err))?;
+                uploads.push(upload);
+                Ok(topology)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Some((GpuNormals::new(device.clone())?, topologies))
+    };
+
+    // Replaces the levels of detail while `--stress` is running. The upload has to be waited for
+    // before the level is drawn.
+    let stress_lod = |level| {
+        let (vertices, mut indices) = icosphere(level);
+        optimize_indices(&mut indices, vertices.len());
+        let indices = Indices::compact(indices, vertices.len());
+        let (lod, upload) = Lod::new(
+            transfer_queue.clone(),
+            &vertices,
+            indices,
+            std::f32::INFINITY,
         )
-        .expect("failed to create swapchain")
+        .expect("failed to create buffer");
+        // Signals the graphics queue once the transfer queue is done
+        let upload = upload
+            .then_signal_semaphore_and_flush()
+            .expect("failed to upload the mesh");
+        (lod, upload)
+    };
+    let mut stress = if config.stress {
+        let stress = Stress::new();
+        let (lod, upload) = stress_lod(stress.level());
+        uploads.push(Box::new(upload));
+        Some((stress, lod))
+    } else {
+        None
     };
 
-    let (vertices, indices) = icosphere(6);
-    let vertex_buffer = {
-        CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), vertices.iter().cloned())
-            .expect("failed to create buffer")
+    if bounds.is_none() {
+        return Err("the mesh has no vertices".into());
+    }
+    // The axis-aligned bounding box of every copy, toggled with B. The box of the rotated mesh
+    // is built for the rotation it's drawn with whenever that changes.
+    let mut show_bounds = false;
+    let mut bounds_lines: Option<(Rad<f32>, Arc<CpuAccessibleBuffer<[LineVertex]>>)> = None;
+
+    // Copies of the mesh with some space between them
+    let instances = {
+        let size = bounds.map_or(0.0, |aabb| {
+            let size = aabb.max - aabb.min;
+            size.x.max(size.y).max(size.z)
+        });
+        instances::grid(config.grid, 1.5 * size, config.instance_colors)
     };
-    let index_buffer =
-        CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), indices.iter().cloned())
-            .expect("failed to create buffer");
+    // Every copy has its own level of detail, which it was last drawn with. The copies are
+    // grouped by it into a buffer of instances for each level every frame.
+    let mut instance_lods = vec![0; instances.len()];
+    let instance_pool = CpuBufferPool::<InstanceData>::vertex_buffer(device.clone());
+
+    // Beyond this distance a copy of the sphere is drawn as a ray-traced impostor instead of a
+    // mesh. The impostor can only stand in for a sphere.
+    let impostor_distance = if config.gltf.is_some() {
+        std::f32::INFINITY
+    } else {
+        40.0
+    };
+    let impostor_corners = {
+        let (buffer, upload) = ImmutableBuffer::from_iter(
+            IMPOSTOR_CORNERS.iter().cloned(),
+            BufferUsage::vertex_buffer(),
+            transfer_queue.clone(),
+        )
+        .map_err(|err| format!("failed to create buffer: {}", err))?;
+        uploads.push(Box::new(upload));
+        buffer
+    };
+
+    // The meshes and impostors are textured with the colors of their texture coordinates, or with
+    // a checkerboard that shows how the texture is stretched with --uv-test
+    let (texture, texture_upload) = if config.uv_test {
+        let texels = checkerboard(config.uv_test_tiles, config.uv_test_colors);
+        texture::upload(transfer_queue.clone(), texels, Format::R8G8B8A8Srgb)?
+    } else {
+        texture::upload(transfer_queue.clone(), uv_gradient(), Format::R8G8B8A8Unorm)?
+    };
+    uploads.push(texture_upload);
+    let sampler = Sampler::simple_repeat_linear_no_mipmap(device.clone());
+
+    // Only the first `drawn_fraction` of the triangles in the index buffer are drawn, which shows
+    // the order in which they are drawn. It changes by `fraction_growth` every second.
+    let mut drawn_fraction: f32 = 1.0;
+    let mut fraction_growth = 0.0;
+
+    // Every triangle is moved `explode_distance` along its normal; the exploded vertex buffer is
+    // rebuilt for the current level of detail whenever that or the distance changes. The distance
+    // changes by `explode_growth` every second.
+    let mut explode_distance: f32 = 0.0;
+    let mut explode_growth = 0.0;
+    let mut exploded: Option<(usize, f32, Arc<CpuAccessibleBuffer<[Vertex]>>)> = None;
+
+    // Draws the mesh as translucent glass; toggled with M
+    let mut glass = false;
+
+    // Draws only the edges of the triangles; toggled with F
+    let mut wireframe = false;
+
+    // Colors the triangles by their area (toggled with Z) or by their order in the index buffer
+    // before and after the vertex cache optimization (cycled with I) instead of shading them; the
+    // heat map vertex buffer is built for the current level of detail when either changes
+    let mut show_heatmap: Option<Heatmap> = None;
+    let mut heatmap: Option<(usize, Heatmap, Arc<CpuAccessibleBuffer<[LineVertex]>>)> = None;
 
-    let vs = shaders::vs::Shader::load(device.clone()).expect("failed to create shader module");
-    let fs = shaders::fs::Shader::load(device.clone()).expect("failed to create shader module");
+    // Interpolates between the flat-faced icosahedron at 0 and the sphere at 1. The vertex buffer
+    // is rebuilt every frame while it's in between.
+    let mut morph: f32 = 1.0;
+    let mut morph_speed: f32 = 0.0;
 
-    // TODO: pull out into a `Camera`; also handle swapchain recreations
-    let mut proj = cgmath::perspective(
-        Rad(std::f32::consts::FRAC_PI_2),
-        { dimensions[0] as f32 / dimensions[1] as f32 },
-        0.01,
-        100.0,
+    // When enabled, everything behind the clip plane is cut away to look inside the mesh. The
+    // plane's normal is rotated `clip_angle` around the y axis and the plane is moved
+    // `clip_offset` along it; both change by their `_speed` every second.
+    let mut clip_enabled = false;
+    let mut clip_angle = Rad(0.0);
+    let mut clip_angle_speed = Rad(0.0);
+    let mut clip_offset = 0.0;
+    let mut clip_offset_speed = 0.0;
+
+    // The mesh slowly turns around the y axis by `rotation_speed` every second, which makes it
+    // easier to judge the shading; paused with R
+    let mut rotation = Rad(0.0);
+    let rotation_speed = Rad(0.5);
+    let mut rotation_paused = false;
+
+    // The clip plane is implemented with `gl_ClipDistance`, which needs the `shader_clip_distance`
+    // feature. Without it the mesh is drawn with a vertex shader that doesn't clip.
+    let clip_supported = device.enabled_features().shader_clip_distance;
+    if !clip_supported {
+        warn!("the device doesn't support clip distances; the clip plane is disabled");
+    }
+    let clip_vs = if clip_supported {
+        Some(
+            shaders::vs::Shader::load(device.clone())
+                .map_err(|err| format!("failed to create shader module: {}", err))?,
+        )
+    } else {
+        None
+    };
+    let no_clip_vs = shaders::vs_no_clip::Shader::load(device.clone())
+        .map_err(|err| format!("failed to create shader module: {}", err))?;
+    let fs = shaders::fs::Shader::load(device.clone())
+        .map_err(|err| format!("failed to create shader module: {}", err))?;
+    let impostor_vs = shaders::impostor_vs::Shader::load(device.clone())
+        .map_err(|err| format!("failed to create shader module: {}", err))?;
+    let impostor_fs = shaders::impostor_fs::Shader::load(device.clone())
+        .map_err(|err| format!("failed to create shader module: {}", err))?;
+    let glass_fs = shaders::glass_fs::Shader::load(device.clone())
+        .map_err(|err| format!("failed to create shader module: {}", err))?;
+    let line_vs = shaders::line_vs::Shader::load(device.clone())
+        .map_err(|err| format!("failed to create shader module: {}", err))?;
+    let line_fs = shaders::line_fs::Shader::load(device.clone())
+        .map_err(|err| format!("failed to create shader module: {}", err))?;
+
+    // In front of the closest copy of the mesh
+    let start_z = instances
+        .iter()
+        .map(|instance| instance.translation().z)
+        .fold(0.0, f32::min)
+        - 3.0;
+    let mut camera = Camera::new(
+        Point3::new(0.0, 0.0, start_z),
+        dimensions,
+        config.look_sensitivity,
     );
-    let mut look_at_dir;
-    let mut pos = Point3::new(0.0, 0.0, -3.0);
-    let mut view;
-
-    let mut delta = Vector3::new(0.0, 0.0, 0.0);
-    let mut rotation = Quaternion::from(Euler {
-        x: Deg(0.0),
-        y: Deg(0.0),
-        z: Deg(0.0),
-    });
+
+    // Sum of the camera space directions of the held movement keys
+    let mut direction = Vector3::new(0.0, 0.0, 0.0);
+    let speed = config.move_speed;
+    let mut movement = Movement::new(config.smooth_movement, config.acceleration, config.damping);
+    // Keeps the altitude while moving; toggled with X
+    let mut walk_mode = false;
+    // Mouse look only works while the cursor is grabbed; toggled with Tab. The cursor is also
+    // released while the window isn't focused.
+    let mut cursor_grabbed = true;
+    let mut focused = true;
+    // Keeps the camera facing the origin, overriding the mouse; toggled with O
+    let mut look_at_origin = false;
 
     let uniform_buffer =
         CpuBufferPool::<shaders::vs::ty::Data>::new(device.clone(), BufferUsage::all());
+    let line_uniform_buffer =
+        CpuBufferPool::<shaders::line_vs::ty::Data>::new(device.clone(), BufferUsage::all());
+    let impostor_uniform_buffer =
+        CpuBufferPool::<shaders::impostor_vs::ty::Data>::new(device.clone(), BufferUsage::all());
+    let lights_buffer =
+        CpuBufferPool::<shaders::fs::ty::Lights>::new(device.clone(), BufferUsage::all());
+    let mut lights = Lights::default();
+    // Rotation speeds of the primary light in radians per second, changed by the arrow keys
+    let mut light_azimuth_speed = 0.0;
+    let mut light_elevation_speed = 0.0;
+
+    // With a non-opaque composite alpha the background should show through the window
+    // The color is linear; this is about 0.1 after gamma encoding
+    let clear_color = if alpha == CompositeAlpha::Opaque {
+        [0.01, 0.01, 0.01, 1.0]
+    } else {
+        [0.0, 0.0, 0.0, 0.0]
+    };
+
+    // Multisampling needs the same number of samples in the color and the depth attachment. The
+    // sample count bits are the counts themselves. The pipelines rasterize with the sample count
+    // of the subpass they're built for.
+    let samples = {
+        let limits = physical.limits();
+        let supported =
+            limits.framebuffer_color_sample_counts() & limits.framebuffer_depth_sample_counts();
+        let samples = [8, 4, 2]
+            .iter()
+            .cloned()
+            .find(|&n| n <= config.msaa && supported & n != 0)
+            .unwrap_or(1);
+        if samples != config.msaa {
+            warn!(
+                "{} samples per pixel aren't supported, using {}",
+                config.msaa, samples
+            );
+        }
+        println!("MSAA samples: {}", samples);
+        samples
+    };
+
+    let render_pass: Arc<dyn RenderPassAbstract + Send + Sync> = if samples > 1 {
+        Arc::new(
+            single_pass_renderpass!(device.clone(),
+                attachments: {
+                    // Drawn into with multiple samples per pixel, which are averaged into `color`
+                    // at the end of the render pass
+                    msaa_color: {
+                        load: Clear,
+                        store: DontCare,
+                        format: swapchain.format(),
+                        samples: samples,
+                    },
+                    color: {
+                        load: DontCare,
+                        store: Store,
+                        format: swapchain.format(),
+                        samples: 1,
+                    },
+                    depth: {
+                        load: Clear,
+                        store: DontCare,
+                        format: Format::D16Unorm,
+                        samples: samples,
+                    }
+                },
+                pass: {
+                    color: [msaa_color],
+                    depth_stencil: {depth},
+                    resolve: [color]
+                }
+            )
+            .map_err(|err| format!("failed to create render pass: {}", err))?,
+        )
+    } else {
+        Arc::new(
+            single_pass_renderpass!(device.clone(),
+                attachments: {
+                    // `color` is a custom name we give to the first attachment.
+                    color: {
+                        load: Clear,
+                        store: Store,
+                        format: swapchain.format(),
+                        samples: 1,
+                    },
+                    // Stored to be copied into a depth shot
+                    depth: {
+                        load: Clear,
+                        store: Store,
+                        format: Format::D16Unorm,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    // We use the attachment named `color` as the one and only color attachment.
+                    color: [color],
+                    depth_stencil: {depth}
+                }
+            )
+            .map_err(|err| format!("failed to create render pass: {}", err))?,
+        )
+    };
+    // One per attachment of the render pass, in the same order
+    let clear_values: Vec<ClearValue> = if samples > 1 {
+        vec![clear_color.into(), ClearValue::None, 1f32.into()]
+    } else {
+        vec![clear_color.into(), 1f32.into()]
+    };
 
-    let render_pass = Arc::new(
-        single_pass_renderpass!(device.clone(),
-            attachments: {
-                // `color` is a custom name we give to the first and only attachment.
-                color: {
-                    load: Clear,
-                    store: Store,
-                    format: swapchain.format(),
-                    samples: 1, // TODO: Figure out if MSAA is possible atm
+    // The two mesh vertex shaders have different types, so the pipelines are built for each of
+    // them and only their trait objects are kept
+    macro_rules! with_mesh_vs {
+        ($vs:ident => $build:expr) => {
+            match clip_vs {
+                Some(ref $vs) => $build,
+                None => {
+                    let $vs = &no_clip_vs;
+                    $build
                 }
-            },
-            pass: {
-                // We use the attachment named `color` as the one and only color attachment.
-                color: [color],
-                // No depth-stencil attachment is indicated with empty brackets.
-                depth_stencil: {}
             }
+        };
+    }
+
+    let mesh_pipeline =
+        |wireframe: bool| -> Result<Arc<dyn GraphicsPipelineAbstract + Send + Sync>, String> {
+            with_mesh_vs!(vs => {
+            let builder = GraphicsPipeline::start()
+                // We need to indicate the layout of the vertices: one buffer with a `Vertex` per
+                // vertex and one with an `InstanceData` per copy of the mesh.
+                .vertex_input(OneVertexOneInstanceDefinition::<Vertex, InstanceData>::new())
+                // A Vulkan shader can in theory contain multiple entry points, so we have to specify
+                // which one. The `main` word of `main_entry_point` actually corresponds to the name of
+                // the entry point.
+                .vertex_shader(vs.main_entry_point(), ())
+                .triangle_list();
+            let builder = if wireframe {
+                builder.polygon_mode_line()
+            } else {
+                builder
+            };
+            builder
+                // The triangles are counter-clockwise from the outside, but the view matrix mirrors
+                // the image (see `Camera`), so they are clockwise on screen
+                .front_face_clockwise()
+                .cull_mode_back()
+                // Use a resizable viewport set to draw over the entire window
+                .viewports_dynamic_scissors_irrelevant(1)
+                .fragment_shader(fs.main_entry_point(), ())
+                .depth_stencil_simple_depth()
+                // We have to indicate which subpass of which render pass this pipeline is going to
+                // be used in. The pipeline will only be usable from this particular subpass.
+                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                .build(device.clone())
+                .map(|pipeline| Arc::new(pipeline) as Arc<_>)
+                .map_err(|err| format!("failed to create pipeline: {}", err))
+            })
+        };
+    let pipeline = mesh_pipeline(false)?;
+    // Drawing only the edges of the triangles needs the `fill_mode_non_solid` feature
+    let wireframe_pipeline = if device.enabled_features().fill_mode_non_solid {
+        Some(mesh_pipeline(true)?)
+    } else {
+        None
+    };
+
+    // Draws a quad for every instance
+    let impostor_pipeline = GraphicsPipeline::start()
+        .vertex_input(OneVertexOneInstanceDefinition::<ImpostorCorner, InstanceData>::new())
+        .vertex_shader(impostor_vs.main_entry_point(), ())
+        .triangle_strip()
+        .viewports_dynamic_scissors_irrelevant(1)
+        .fragment_shader(impostor_fs.main_entry_point(), ())
+        .depth_stencil_simple_depth()
+        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+        .build(device.clone())
+        .map(Arc::new)
+        .map_err(|err| format!("failed to create pipeline: {}", err))?;
+
+    // Used for debug overlays like the bounding box, which are drawn for every instance
+    let line_pipeline = GraphicsPipeline::start()
+        .vertex_input(OneVertexOneInstanceDefinition::<LineVertex, InstanceData>::new())
+        .vertex_shader(line_vs.main_entry_point(), ())
+        .line_list()
+        .viewports_dynamic_scissors_irrelevant(1)
+        .fragment_shader(line_fs.main_entry_point(), ())
+        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+        .build(device.clone())
+        .map(Arc::new)
+        .map_err(|err| format!("failed to create pipeline: {}", err))?;
+
+    // Translucent glass; drawing the back faces before the front faces blends them in the right
+    // order without sorting because the sphere is convex
+    let glass_pipelines = [true, false]
+        .iter()
+        .map(
+            |&back_faces| -> Result<Arc<dyn GraphicsPipelineAbstract + Send + Sync>, String> {
+                with_mesh_vs!(vs => {
+                let builder = GraphicsPipeline::start()
+                    .vertex_input(OneVertexOneInstanceDefinition::<Vertex, InstanceData>::new())
+                    .vertex_shader(vs.main_entry_point(), ())
+                    .triangle_list()
+                    .front_face_clockwise();
+                let builder = if back_faces {
+                    builder.cull_mode_front()
+                } else {
+                    builder.cull_mode_back()
+                };
+                builder
+                    .viewports_dynamic_scissors_irrelevant(1)
+                    .fragment_shader(glass_fs.main_entry_point(), ())
+                    // Test against opaque geometry, but don't hide the glass behind itself
+                    .depth_stencil_simple_depth()
+                    .depth_write(false)
+                    .blend_alpha_blending()
+                    .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                    .build(device.clone())
+                    .map(|pipeline| Arc::new(pipeline) as Arc<_>)
+                    .map_err(|err| format!("failed to create pipeline: {}", err))
+                })
+            },
         )
-        .unwrap(),
-    );
+        .collect::<Result<Vec<_>, _>>()?;
 
-    let pipeline = Arc::new(
-        GraphicsPipeline::start()
-            // We need to indicate the layout of the vertices.
-            // The type `SingleBufferDefinition` actually contains a template parameter corresponding
-            // to the type of each vertex. But in this code it is automatically inferred.
-            .vertex_input_single_buffer()
-            // A Vulkan shader can in theory contain multiple entry points, so we have to specify
-            // which one. The `main` word of `main_entry_point` actually corresponds to the name of
-            // the entry point.
-            .vertex_shader(vs.main_entry_point(), ())
-            .triangle_list()
-            // .polygon_mode_line()
-            .front_face_counter_clockwise()
-            .cull_mode_back()
-            // Use a resizable viewport set to draw over the entire window
-            .viewports_dynamic_scissors_irrelevant(1)
-            .fragment_shader(fs.main_entry_point(), ())
-            // We have to indicate which subpass of which render pass this pipeline is going to be used
-            // in. The pipeline will only be usable from this particular subpass.
-            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
-            .build(device.clone())
-            .unwrap(),
-    );
+    // Draws the triangles of the heat maps with the colors of their vertices and no lighting
+    let heatmap_pipeline = GraphicsPipeline::start()
+        .vertex_input(OneVertexOneInstanceDefinition::<LineVertex, InstanceData>::new())
+        .vertex_shader(line_vs.main_entry_point(), ())
+        .triangle_list()
+        .front_face_clockwise()
+        .cull_mode_back()
+        .viewports_dynamic_scissors_irrelevant(1)
+        .fragment_shader(line_fs.main_entry_point(), ())
+        .depth_stencil_simple_depth()
+        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+        .build(device.clone())
+        .map(Arc::new)
+        .map_err(|err| format!("failed to create pipeline: {}", err))?;
 
     // The render pass we created above only describes the layout of our framebuffers. Before we
     // can draw we also need to create the actual framebuffers.
     //
     // Since we need to draw to multiple images, we are going to create a different framebuffer for
     // each image.
-    let mut framebuffers: Option<Vec<Arc<Framebuffer<_, _>>>> = None;
+    let mut framebuffers: Option<Vec<Arc<dyn FramebufferAbstract + Send + Sync>>> = None;
+    // The depth attachments of the framebuffers
+    let mut depth_images = Vec::new();
+
+    // The depth buffer of the next frame is written to this PNG file if it's set; with
+    // --depth-shot for the first frame, and by pressing J. Multisampled images can't be copied.
+    let depth_shot_path = config
+        .depth_shot
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("depth.png"));
+    let mut take_depth_shot = config.depth_shot.is_some();
+    if take_depth_shot && samples > 1 {
+        warn!("depth shots need --msaa 1");
+        take_depth_shot = false;
+    }
 
     // Initialization is finally finished!
 
@@ -293,6 +1000,8 @@ fn main() {
     // To continue rendering, we need to recreate the swapchain by creating a new swapchain.
     // Here, we remember that we need to do this for the next loop iteration.
     let mut recreate_swapchain = false;
+    // The viewport covers the whole window, so it only changes with the swapchain
+    let mut dynamic_state = full_viewport(dimensions);
 
     // In the loop below we are going to submit commands to the GPU. Submitting a command produces
     // an object that implements the `GpuFuture` trait, which holds the resources for as long as
@@ -300,12 +1009,48 @@ fn main() {
     //
     // Destroying the `GpuFuture` blocks until the GPU is finished executing it. In order to avoid
     // that, we store the submission of the previous frame here.
-    let mut previous_frame_end = Box::new(now(device.clone())) as Box<GpuFuture>;
+    //
+    // The first frame has to wait for the meshes to be uploaded.
+    let upload = uploads
+        .into_iter()
+        .fold(
+            Box::new(now(device.clone())) as Box<GpuFuture>,
+            |all, upload| Box::new(all.join(upload)),
+        )
+        .then_signal_semaphore_and_flush()
+        .map_err(|err| format!("failed to upload the meshes: {}", err))?;
+    let mut previous_frame_end = Box::new(upload) as Box<GpuFuture>;
 
     let mut last_sec = Instant::now();
-    let mut fps = 0;
+    let mut last_frame = Instant::now();
+    let mut stats = FrameStats::default();
+    // Times of all submitted frames, for the summary printed with `--frames`
+    let mut frame_times = FrameTimes::default();
+    let mut timing_csv = match config.timing_csv {
+        Some(ref path) => Some(
+            TimingCsv::create(path)
+                .map_err(|err| format!("failed to create {}: {}", path.display(), err))?,
+        ),
+        None => None,
+    };
+
+    // Set when the GPU was reset or crashed. The device can't be recreated for the same window:
+    // waiting for the futures of a lost device panics, and leaking them keeps the old swapchain of
+    // the window alive. So the app exits instead of failing to submit frames forever. A failed
+    // submission drops the future of the previous frame inside vulkano, which waits for it, so
+    // that can still panic before the error gets here.
+    let mut device_lost = false;
 
     loop {
+        if device_lost {
+            if let Some(ref mut csv) = timing_csv {
+                csv.flush().expect("failed to write timing CSV");
+            }
+            // Dropping the future would wait for the lost device
+            mem::forget(previous_frame_end);
+            return Err("lost the connection to the device (e.g. the GPU was reset)".into());
+        }
+
         // It is important to call this function from time to time, otherwise resources will keep
         // accumulating and you will eventually reach an out of memory error.
         // Calling this function polls various fences in order to determine what the GPU has
@@ -313,22 +1058,25 @@ fn main() {
         previous_frame_end.cleanup_finished();
 
         let now = Instant::now();
+        let dt = duration_secs(now - last_frame);
+        last_frame = now;
         if now - last_sec >= Duration::from_secs(1) {
-            println!("fps: {}", fps);
+            surface.window().set_title(&stats.title());
+            if config.stats {
+                println!("{}", stats);
+            }
             last_sec = now;
-            fps = 0;
-        } else {
-            fps += 1;
+            stats = FrameStats::default();
         }
+        stats.add_frame(dt);
 
         // If the swapchain needs to be recreated, recreate it
         if recreate_swapchain {
             // Get the new dimensions for the viewport/framebuffers.
-            dimensions = surface
+            let caps = surface
                 .capabilities(physical)
-                .expect("failed to get surface capabilities")
-                .current_extent
-                .unwrap();
+                .expect("failed to get surface capabilities");
+            dimensions = swapchain_extent(&caps, surface.window());
 
             let (new_swapchain, new_images) = match swapchain.recreate_with_dimension(dimensions) {
                 Ok(r) => r,
@@ -340,12 +1088,8 @@ fn main() {
                 Err(err) => panic!("{:?}", err),
             };
 
-            proj = cgmath::perspective(
-                Rad(std::f32::consts::FRAC_PI_2),
-                { dimensions[0] as f32 / dimensions[1] as f32 },
-                0.01,
-                100.0,
-            );
+            camera.resize(dimensions);
+            dynamic_state = full_viewport(dimensions);
 
             swapchain = new_swapchain;
             images = new_images;
@@ -358,46 +1102,259 @@ fn main() {
         // Because framebuffers contains an Arc on the old swapchain, we need to
         // recreate framebuffers as well.
         if framebuffers.is_none() {
+            // Have to match the size of the swapchain images, so they're recreated with them
+            depth_images = images
+                .iter()
+                .map(|_| {
+                    if samples > 1 {
+                        AttachmentImage::transient_multisampled(
+                            device.clone(),
+                            dimensions,
+                            samples,
+                            Format::D16Unorm,
+                        )
+                    } else {
+                        // Copied into depth shots
+                        let usage = ImageUsage {
+                            depth_stencil_attachment: true,
+                            transfer_source: true,
+                            ..ImageUsage::none()
+                        };
+                        AttachmentImage::with_usage(
+                            device.clone(),
+                            dimensions,
+                            Format::D16Unorm,
+                            usage,
+                        )
+                    }
+                    .expect("failed to create depth image")
+                })
+                .collect::<Vec<_>>();
             let new_framebuffers = Some(
                 images
                     .iter()
-                    .map(|image| {
-                        Arc::new(
-                            Framebuffer::start(render_pass.clone())
-                                .add(image.clone())
-                                .unwrap()
-                                .build()
-                                .unwrap(),
-                        )
+                    .zip(&depth_images)
+                    .map(|(image, depth_image)| {
+                        let depth_image = depth_image.clone();
+                        if samples > 1 {
+                            let msaa_image = AttachmentImage::transient_multisampled(
+                                device.clone(),
+                                dimensions,
+                                samples,
+                                swapchain.format(),
+                            )
+                            .expect("failed to create multisampled image");
+                            Arc::new(
+                                Framebuffer::start(render_pass.clone())
+                                    .add(msaa_image)
+                                    .unwrap()
+                                    .add(image.clone())
+                                    .unwrap()
+                                    .add(depth_image)
+                                    .unwrap()
+                                    .build()
+                                    .unwrap(),
+                            )
+                                as Arc<dyn FramebufferAbstract + Send + Sync>
+                        } else {
+                            Arc::new(
+                                Framebuffer::start(render_pass.clone())
+                                    .add(image.clone())
+                                    .unwrap()
+                                    .add(depth_image)
+                                    .unwrap()
+                                    .build()
+                                    .unwrap(),
+                            )
+                        }
                     })
                     .collect::<Vec<_>>(),
             );
             mem::replace(&mut framebuffers, new_framebuffers);
         }
 
-        look_at_dir = rotation * Vector3::new(0.0, 0.0, 1.0);
-        let up = rotation * Vector3::new(0.0, -1.0, 0.0);
-        pos += rotation * delta;
-        view = Matrix4::look_at_dir(pos, look_at_dir, up);
+        drawn_fraction = (drawn_fraction + fraction_growth * dt).max(0.0).min(1.0);
+        explode_distance = (explode_distance + explode_growth * dt).max(0.0);
+        clip_angle += clip_angle_speed * dt;
+        if !rotation_paused {
+            rotation = (rotation + rotation_speed * dt).normalize();
+        }
+        if light_azimuth_speed != 0.0 || light_elevation_speed != 0.0 {
+            lights.rotate_primary(
+                Rad(light_azimuth_speed * dt),
+                Rad(light_elevation_speed * dt),
+            );
+        }
+        morph = (morph + morph_speed * dt).max(0.0).min(1.0);
+        if morph == 0.0 || morph == 1.0 {
+            morph_speed = 0.0;
+        }
+        clip_offset += clip_offset_speed * dt;
+
+        // Moving diagonally isn't any faster
+        let target = if direction.is_zero() {
+            direction
+        } else {
+            direction.normalize_to(speed)
+        };
+        let displacement = movement.update(target, dt);
+        if walk_mode {
+            camera.position += walk(camera.orientation(), displacement);
+        } else {
+            camera.apply_movement(displacement);
+        }
+        if look_at_origin {
+            camera.look_at(Point3::origin());
+        }
+        let view = camera.view_matrix();
+        let proj = camera.proj_matrix();
+        stats.set_camera_pos(camera.position);
+
+        // The closest copy needs the most detail
+        let distance = nearest_distance(&instances, camera.position);
+        let lod_index = lods.select_lod(distance);
+        // Every copy gets the level for its own distance, or an impostor if it's far enough away
+        let mut lod_instances = vec![Vec::new(); lods.num_lods()];
+        let mut impostor_instances = Vec::new();
+        for (instance, level) in instances.iter().zip(&mut instance_lods) {
+            let distance = instance.distance(camera.position);
+            *level = lods.update_lod(*level, distance);
+            if distance > impostor_distance && stress.is_none() {
+                impostor_instances.push(instance.clone());
+            } else {
+                lod_instances[*level].push(instance.clone());
+            }
+        }
+        let lod = match stress {
+            Some((_, ref lod)) => lod,
+            None => lods.lod(lod_index),
+        };
+        if explode_distance == 0.0 || stress.is_some() {
+            exploded = None;
+        } else if exploded.as_ref().map_or(true, |&(index, distance, _)| {
+            index != lod_index || distance != explode_distance
+        }) {
+            let mesh = &meshes[lod_index];
+            let vertices = explode(mesh.vertices(), mesh.indices(), explode_distance);
+            let buffer = CpuAccessibleBuffer::from_iter(
+                device.clone(),
+                BufferUsage::all(),
+                vertices.into_iter(),
+            )
+            .expect("failed to create buffer");
+            exploded = Some((lod_index, explode_distance, buffer));
+        }
+
+        match show_heatmap {
+            Some(shown) if stress.is_none() => {
+                if heatmap.as_ref().map_or(true, |&(index, kind, _)| {
+                    index != lod_index || kind != shown
+                }) {
+                    let mesh = &meshes[lod_index];
+                    let vertices = match shown {
+                        Heatmap::Area => area_heatmap(mesh.vertices(), mesh.indices()),
+                        Heatmap::IndexOrder { optimized: true } => {
+                            index_order_heatmap(mesh.vertices(), mesh.indices())
+                        }
+                        Heatmap::IndexOrder { optimized: false } => {
+                            index_order_heatmap(mesh.vertices(), &unoptimized_indices[lod_index])
+                        }
+                    };
+                    let buffer = CpuAccessibleBuffer::from_iter(
+                        device.clone(),
+                        BufferUsage::all(),
+                        vertices.into_iter(),
+                    )
+                    .expect("failed to create buffer");
+                    heatmap = Some((lod_index, shown, buffer));
+                }
+            }
+            _ => heatmap = None,
+        }
+
+        // The morphed vertices change every frame, so they stay in host-visible memory
+        let morphing = morph < 1.0 && stress.is_none();
+        let vertex_buffer: Arc<dyn TypedBufferAccess<Content = [Vertex]> + Send + Sync> =
+            if morphing {
+                let from = &morph_targets[lod_index];
+                let to = meshes[lod_index].vertices();
+                let mut vertices: Vec<Vertex> = from
+                    .iter()
+                    .zip(to)
+                    .map(|(from, to)| {
+                        let from_position = Vector3::from(from.position());
+                        let position =
+                            from_position + morph * (Vector3::from(to.position()) - from_position);
+                        // Both have the same texture coordinates. The normals are computed below.
+                        Vertex::new(position.into(), to.normal(), to.uv())
+                    })
+                    .collect();
+                // Interpolated normals don't fit the interpolated shape. With --gpu-normals they
+                // are computed in the command buffer instead.
+                if gpu_normals.is_none() {
+                    compute_normals(&mut vertices, meshes[lod_index].indices());
+                }
+                CpuAccessibleBuffer::from_iter(
+                    device.clone(),
+                    BufferUsage::all(),
+                    vertices.into_iter(),
+                )
+                .expect("failed to create buffer")
+            } else {
+                lod.vertex_buffer.clone()
+            };
 
         let uniform_buffer_subbuffer = {
             let uniform_data = shaders::vs::ty::Data {
-                world: Matrix4::identity().into(),
+                world: Matrix4::from_angle_y(rotation).into(),
                 view: view.into(),
                 proj: proj.into(),
+                clip_plane: if clip_enabled {
+                    let normal = Vector3::new(clip_angle.cos(), 0.0, clip_angle.sin());
+                    normal.extend(-clip_offset).into()
+                } else {
+                    [0.0, 0.0, 0.0, 1.0]
+                },
             };
 
             uniform_buffer.next(uniform_data).unwrap()
         };
+        let lights_subbuffer = lights_buffer.next(lights.uniform()).unwrap();
 
+        let pipeline = match wireframe_pipeline {
+            Some(ref wireframe_pipeline) if wireframe => wireframe_pipeline,
+            _ => &pipeline,
+        };
         let set = Arc::new(
             PersistentDescriptorSet::start(pipeline.clone(), 0)
-                .add_buffer(uniform_buffer_subbuffer)
+                .add_buffer(uniform_buffer_subbuffer.clone())
+                .unwrap()
+                .add_buffer(lights_subbuffer.clone())
+                .unwrap()
+                .add_image(texture.clone())
+                .unwrap()
+                .add_sampler(sampler.clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+        // The overlays are already in world space
+        let line_uniform_subbuffer = line_uniform_buffer
+            .next(shaders::line_vs::ty::Data {
+                world: Matrix4::identity().into(),
+                view: view.into(),
+                proj: proj.into(),
+            })
+            .unwrap();
+        let line_set = Arc::new(
+            PersistentDescriptorSet::start(line_pipeline.clone(), 0)
+                .add_buffer(line_uniform_subbuffer)
                 .unwrap()
                 .build()
                 .unwrap(),
         );
 
+        let acquire_start = Instant::now();
         let (image_num, acquire_future) =
             match swapchain::acquire_next_image(swapchain.clone(), None) {
                 Ok(r) => r,
@@ -405,54 +1362,289 @@ fn main() {
                     recreate_swapchain = true;
                     continue;
                 }
+                Err(AcquireError::DeviceLost) => {
+                    device_lost = true;
+                    continue;
+                }
                 Err(err) => panic!("{:?}", err),
             };
+        let record_start = Instant::now();
+        stats.add_acquire_time(duration_secs(record_start - acquire_start));
+        let triangles_before = stats.triangles();
 
-        let command_buffer =
+        let mut command_buffer_builder =
             AutoCommandBufferBuilder::primary_one_time_submit(device.clone(), queue.family())
-                .unwrap()
-                // Before we can draw, we have to *enter a render pass*. There are two methods to do
-                // this: `draw_inline` and `draw_secondary`. The latter is a bit more advanced and is
-                // not covered here.
-                //
-                // The third parameter builds the list of values to clear the attachments with. The API
-                // is similar to the list of attachments when building the framebuffers, except that
-                // only the attachments that use `load: Clear` appear in the list.
-                .begin_render_pass(
-                    framebuffers.as_ref().unwrap()[image_num].clone(),
-                    false,
-                    vec![[0.1, 0.1, 0.1, 1.0].into()],
+                .unwrap();
+        // Dispatched before the render pass, which can't contain dispatches. The pipeline barrier
+        // that makes the draws wait for the normals is inserted by the command buffer builder.
+        let vertex_buffer = match gpu_normals {
+            Some((ref gpu_normals, ref topologies)) if morphing => {
+                let (builder, vertex_buffer) = gpu_normals
+                    .record(
+                        command_buffer_builder,
+                        vertex_buffer,
+                        &topologies[lod_index],
+                    )
+                    .expect("failed to compute the normals");
+                command_buffer_builder = builder;
+                vertex_buffer as Arc<_>
+            }
+            _ => vertex_buffer,
+        };
+        command_buffer_builder = command_buffer_builder
+            // Before we can draw, we have to *enter a render pass*. There are two methods to do
+            // this: `draw_inline` and `draw_secondary`. The latter is a bit more advanced and is
+            // not covered here.
+            //
+            // The third parameter is the list of values to clear the attachments with, in the
+            // same order as the attachments of the framebuffer. Attachments that don't use
+            // `load: Clear` get `ClearValue::None`.
+            .begin_render_pass(
+                framebuffers.as_ref().unwrap()[image_num].clone(),
+                false,
+                clear_values.clone(),
+            )
+            .unwrap();
+
+        // We are now inside the first subpass of the render pass. We add a draw command.
+        //
+        // The last two parameters contain the list of resources to pass to the shaders.
+        // Since we used an `EmptyPipeline` object, the objects have to be `()`.
+        let drawn_indices = (drawn_fraction * lod.triangles as f32) as usize * 3;
+        if !impostor_instances.is_empty() {
+            let impostor_uniform_subbuffer = impostor_uniform_buffer
+                .next(shaders::impostor_vs::ty::Data {
+                    world: Matrix4::from_angle_y(rotation).into(),
+                    view: view.into(),
+                    proj: proj.into(),
+                    radius,
+                })
+                .unwrap();
+            let impostor_set = Arc::new(
+                PersistentDescriptorSet::start(impostor_pipeline.clone(), 0)
+                    .add_buffer(impostor_uniform_subbuffer)
+                    .unwrap()
+                    .add_buffer(lights_subbuffer.clone())
+                    .unwrap()
+                    .add_image(texture.clone())
+                    .unwrap()
+                    .add_sampler(sampler.clone())
+                    .unwrap()
+                    .build()
+                    .unwrap(),
+            );
+            let num_impostors = impostor_instances.len() as u64;
+            let impostor_instance_buffer = instance_pool
+                .chunk(impostor_instances)
+                .expect("failed to create buffer");
+            command_buffer_builder = command_buffer_builder
+                .draw(
+                    impostor_pipeline.clone(),
+                    &dynamic_state,
+                    (impostor_corners.clone(), impostor_instance_buffer),
+                    impostor_set,
+                    (),
                 )
-                .unwrap()
-                // We are now inside the first subpass of the render pass. We add a draw command.
-                //
-                // The last two parameters contain the list of resources to pass to the shaders.
-                // Since we used an `EmptyPipeline` object, the objects have to be `()`.
-                .draw_indexed(
-                    pipeline.clone(),
-                    &DynamicState {
-                        line_width: None,
-                        // TODO: Find a way to do this without having to dynamically allocate a Vec every frame.
-                        viewports: Some(vec![Viewport {
-                            origin: [0.0, 0.0],
-                            dimensions: [dimensions[0] as f32, dimensions[1] as f32],
-                            depth_range: 0.0..1.0,
-                        }]),
-                        scissors: None,
-                    },
-                    vertex_buffer.clone(),
-                    index_buffer.clone(),
-                    set.clone(),
+                .unwrap();
+            stats.add_draw_call(2 * num_impostors);
+        }
+
+        // The buffers of the debug views are only built for the level of the closest copy, so
+        // while one of them is shown every copy is drawn with that level
+        let mesh_draws: Vec<(&Lod, Arc<dyn BufferAccess + Send + Sync>, Vec<InstanceData>)> =
+            if morphing || exploded.is_some() || heatmap.is_some() || stress.is_some() {
+                let instances = lod_instances.concat();
+                stats.add_lod(lod_index, lods.num_lods(), instances.len() as u32);
+                vec![(lod, Arc::new(vertex_buffer) as Arc<_>, instances)]
+            } else {
+                lod_instances
+                    .into_iter()
+                    .enumerate()
+                    .map(|(level, instances)| {
+                        stats.add_lod(level, lods.num_lods(), instances.len() as u32);
+                        let lod = lods.lod(level);
+                        (lod, lod.vertex_buffer.clone() as Arc<_>, instances)
+                    })
+                    .collect()
+            };
+        let mesh_draws = mesh_draws
+            .into_iter()
+            .filter(|(_, _, instances)| !instances.is_empty());
+
+        if let Some((_, _, ref buffer)) = heatmap {
+            // Stands in for the mesh of every copy
+            let instances = mesh_draws.flat_map(|(_, _, instances)| instances);
+            let instances: Vec<InstanceData> = instances.collect();
+            if drawn_indices > 0 && !instances.is_empty() {
+                let num_instances = instances.len() as u64;
+                let heatmap_set = Arc::new(
+                    PersistentDescriptorSet::start(heatmap_pipeline.clone(), 0)
+                        .add_buffer(uniform_buffer_subbuffer)
+                        .unwrap()
+                        .build()
+                        .unwrap(),
+                );
+                let instance_buffer = instance_pool
+                    .chunk(instances)
+                    .expect("failed to create buffer");
+                command_buffer_builder = command_buffer_builder
+                    .draw(
+                        heatmap_pipeline.clone(),
+                        &dynamic_state,
+                        (
+                            buffer
+                                .clone()
+                                .into_buffer_slice()
+                                .slice(0..drawn_indices)
+                                .unwrap(),
+                            instance_buffer,
+                        ),
+                        heatmap_set,
+                        (),
+                    )
+                    .unwrap();
+                stats.add_draw_call(drawn_indices as u64 / 3 * num_instances);
+            }
+        } else {
+            for (lod, vertex_buffer, instances) in mesh_draws {
+                let drawn_indices = (drawn_fraction * lod.triangles as f32) as usize * 3;
+                if drawn_indices == 0 {
+                    continue;
+                }
+                let num_instances = instances.len() as u64;
+                let instance_buffer = Arc::new(
+                    instance_pool
+                        .chunk(instances)
+                        .expect("failed to create buffer"),
+                ) as Arc<dyn BufferAccess + Send + Sync>;
+                command_buffer_builder = match exploded {
+                    // The exploded buffer has three vertices per triangle in index buffer order
+                    Some((_, _, ref buffer)) => command_buffer_builder
+                        .draw(
+                            pipeline.clone(),
+                            &dynamic_state,
+                            vec![
+                                Arc::new(
+                                    buffer
+                                        .clone()
+                                        .into_buffer_slice()
+                                        .slice(0..drawn_indices)
+                                        .unwrap(),
+                                ) as Arc<_>,
+                                instance_buffer,
+                            ],
+                            set.clone(),
+                            (),
+                        )
+                        .unwrap(),
+                    None if glass => {
+                        let mut builder = command_buffer_builder;
+                        for glass_pipeline in &glass_pipelines {
+                            let glass_set = Arc::new(
+                                PersistentDescriptorSet::start(glass_pipeline.clone(), 0)
+                                    .add_buffer(uniform_buffer_subbuffer.clone())
+                                    .unwrap()
+                                    .add_buffer(lights_subbuffer.clone())
+                                    .unwrap()
+                                    .build()
+                                    .unwrap(),
+                            );
+                            builder = lod
+                                .index_buffer
+                                .draw(
+                                    builder,
+                                    glass_pipeline.clone(),
+                                    &dynamic_state,
+                                    vec![vertex_buffer.clone(), instance_buffer.clone()],
+                                    drawn_indices,
+                                    glass_set,
+                                )
+                                .unwrap();
+                        }
+                        // The second draw is counted below
+                        stats.add_draw_call(drawn_indices as u64 / 3 * num_instances);
+                        builder
+                    }
+                    None => lod
+                        .index_buffer
+                        .draw(
+                            command_buffer_builder,
+                            pipeline.clone(),
+                            &dynamic_state,
+                            vec![vertex_buffer, instance_buffer],
+                            drawn_indices,
+                            set.clone(),
+                        )
+                        .unwrap(),
+                };
+                stats.add_draw_call(drawn_indices as u64 / 3 * num_instances);
+            }
+        }
+
+        if !show_bounds {
+            bounds_lines = None;
+        } else if bounds_lines
+            .as_ref()
+            .map_or(true, |&(angle, _)| angle != rotation)
+        {
+            let world = Matrix3::from_angle_y(rotation);
+            let rotated: Vec<Vertex> = meshes[0]
+                .vertices()
+                .iter()
+                .map(|v| Vertex::from_position((world * Vector3::from(v.position())).into()))
+                .collect();
+            let aabb = bounding_box(&rotated).expect("the mesh has no vertices");
+            let buffer = CpuAccessibleBuffer::from_iter(
+                device.clone(),
+                BufferUsage::all(),
+                aabb_lines(&aabb, [1.0, 1.0, 0.0]).into_iter(),
+            )
+            .expect("failed to create buffer");
+            bounds_lines = Some((rotation, buffer));
+        }
+        if let Some((_, ref buffer)) = bounds_lines {
+            let instance_buffer = instance_pool
+                .chunk(instances.iter().cloned())
+                .expect("failed to create buffer");
+            command_buffer_builder = command_buffer_builder
+                .draw(
+                    line_pipeline.clone(),
+                    &dynamic_state,
+                    (buffer.clone(), instance_buffer),
+                    line_set.clone(),
                     (),
                 )
-                .unwrap()
-                // We leave the render pass by calling `draw_end`. Note that if we had multiple
-                // subpasses we could have called `next_inline` (or `next_secondary`) to jump to the
-                // next subpass.
-                .end_render_pass()
-                .unwrap()
-                .build()
                 .unwrap();
+            stats.add_draw_call(0);
+        }
+
+        command_buffer_builder = command_buffer_builder
+            // We leave the render pass by calling `draw_end`. Note that if we had multiple
+            // subpasses we could have called `next_inline` (or `next_secondary`) to jump to the
+            // next subpass.
+            .end_render_pass()
+            .unwrap();
+        let depth_shot = if take_depth_shot {
+            take_depth_shot = false;
+            let depth_image = depth_images[image_num].clone();
+            let [width, height] = depth_image.dimensions();
+            let buffer = CpuAccessibleBuffer::from_iter(
+                device.clone(),
+                BufferUsage::all(),
+                iter::repeat(0u16).take(width as usize * height as usize),
+            )
+            .expect("failed to create buffer");
+            command_buffer_builder = command_buffer_builder
+                .copy_image_to_buffer(depth_image, buffer.clone())
+                .unwrap();
+            Some(([width, height], buffer))
+        } else {
+            None
+        };
+        let command_buffer = command_buffer_builder.build().unwrap();
+        let submit_start = Instant::now();
+        let record_time = duration_secs(submit_start - record_start);
+        stats.add_record_time(record_time);
 
         let future = previous_frame_end
             .join(acquire_future)
@@ -463,17 +1655,65 @@ fn main() {
 
         match future {
             Ok(future) => {
+                if let Some((dimensions, buffer)) = depth_shot {
+                    // The copy has to be finished before the buffer can be read
+                    match future.wait(None) {
+                        Ok(()) => {
+                            let depth = buffer.read().expect("failed to read the depth shot");
+                            match write_depth_shot(&depth, dimensions, &camera, &depth_shot_path) {
+                                Ok(Some((nearest, farthest))) => println!(
+                                    "Wrote the depth buffer to {}: {:.3} (white) to {:.3} \
+                                     (black) units from the camera",
+                                    depth_shot_path.display(),
+                                    nearest,
+                                    farthest
+                                ),
+                                Ok(None) => println!(
+                                    "Wrote the depth buffer to {}; nothing was drawn",
+                                    depth_shot_path.display()
+                                ),
+                                Err(err) => warn!("{}", err),
+                            }
+                        }
+                        Err(err) => warn!("failed to take the depth shot: {:?}", err),
+                    }
+                }
                 previous_frame_end = Box::new(future) as Box<_>;
             }
             Err(FlushError::OutOfDate) => {
                 recreate_swapchain = true;
                 previous_frame_end = Box::new(vulkano::sync::now(device.clone())) as Box<_>;
             }
+            Err(FlushError::DeviceLost) => {
+                device_lost = true;
+                previous_frame_end = Box::new(vulkano::sync::now(device.clone())) as Box<_>;
+            }
             Err(e) => {
                 println!("{:?}", e);
                 previous_frame_end = Box::new(vulkano::sync::now(device.clone())) as Box<_>;
             }
         }
+        stats.add_submit_time(duration_secs(submit_start.elapsed()));
+        frame_times.add(dt);
+        // Of all copies of the mesh, with their levels of detail
+        let triangles = stats.triangles() - triangles_before;
+        if let Some(ref mut csv) = timing_csv {
+            csv.add_frame(dt, record_time, triangles)
+                .expect("failed to write timing CSV");
+        }
+
+        let mut stress_done = false;
+        if let Some((ref mut stress, ref mut lod)) = stress {
+            match stress.add_frame(dt, triangles) {
+                StressStep::Continue => {}
+                StressStep::Level(level) => {
+                    let (new_lod, upload) = stress_lod(level);
+                    *lod = new_lod;
+                    previous_frame_end = Box::new(previous_frame_end.join(upload));
+                }
+                StressStep::Done => stress_done = true,
+            }
+        }
 
         // Note that in more complex programs it is likely that one of `acquire_next_image`,
         // `command_buffer::submit`, or `present` will block for some time. This happens when the
@@ -493,55 +1733,513 @@ fn main() {
                 event: WindowEvent::Focused(b),
                 ..
             } => {
-                surface.window().hide_cursor(b);
+                focused = b;
+                set_cursor_grab(surface.window(), cursor_grabbed && focused);
             }
+            // Unlike the device events below, window events only arrive while the window is
+            // focused, so these keys don't act on other applications
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                virtual_keycode: Some(key_code),
+                                state: ElementState::Pressed,
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => match key_code {
+                VirtualKeyCode::Escape => done = true,
+                VirtualKeyCode::Tab => {
+                    cursor_grabbed = !cursor_grabbed;
+                    set_cursor_grab(surface.window(), cursor_grabbed && focused);
+                }
+                _ => {}
+            },
+            // `current_extent` and thus the swapchain are in physical pixels; they change not only
+            // on resizes but also when the window is moved to a screen with a different DPI.
+            Event::WindowEvent {
+                event: WindowEvent::Resized(_),
+                ..
+            }
+            | Event::WindowEvent {
+                event: WindowEvent::HiDpiFactorChanged(_),
+                ..
+            } => recreate_swapchain = true,
             Event::DeviceEvent {
                 event:
                     DeviceEvent::Key(KeyboardInput {
                         virtual_keycode: Some(key_code),
                         state,
+                        modifiers,
                         ..
                     }),
                 ..
             } => {
                 let d = match key_code {
-                    VirtualKeyCode::W => Vector3::new(0.0, 0.0, 0.05),
-                    VirtualKeyCode::A => Vector3::new(-0.05, 0.0, 0.0),
-                    VirtualKeyCode::S => Vector3::new(0.0, 0.0, -0.05),
-                    VirtualKeyCode::D => Vector3::new(0.05, 0.0, 0.0),
-                    VirtualKeyCode::E => Vector3::new(0.0, 0.05, 0.0),
-                    VirtualKeyCode::Q => Vector3::new(0.0, -0.05, 0.0),
+                    VirtualKeyCode::LBracket | VirtualKeyCode::RBracket if modifiers.ctrl => {
+                        if state == ElementState::Pressed {
+                            camera.sensitivity *= match key_code {
+                                VirtualKeyCode::LBracket => 0.8,
+                                _ => 1.25,
+                            };
+                            println!("look sensitivity: {:.3}", camera.sensitivity);
+                        }
+                        return;
+                    }
+                    VirtualKeyCode::B => {
+                        if state == ElementState::Pressed {
+                            show_bounds = !show_bounds;
+                        }
+                        return;
+                    }
+                    VirtualKeyCode::Minus | VirtualKeyCode::Equals => {
+                        // Takes 10 seconds to grow through the whole mesh
+                        let growth = match key_code {
+                            VirtualKeyCode::Minus => -0.1,
+                            _ => 0.1,
+                        };
+                        fraction_growth = match state {
+                            ElementState::Pressed => growth,
+                            ElementState::Released => 0.0,
+                        };
+                        return;
+                    }
+                    // Add a point light at the camera, remove the last light or move the last
+                    // point light to the camera
+                    VirtualKeyCode::L | VirtualKeyCode::K | VirtualKeyCode::J => {
+                        if state == ElementState::Pressed {
+                            let changed = match key_code {
+                                VirtualKeyCode::L => lights.add_point(camera.position),
+                                VirtualKeyCode::K => lights.remove_last().is_some(),
+                                _ => lights.move_last(camera.position),
+                            };
+                            if changed {
+                                println!("lights: {}", lights.num_lights());
+                            }
+                        }
+                        return;
+                    }
+                    VirtualKeyCode::Comma | VirtualKeyCode::Period => {
+                        let growth = match key_code {
+                            VirtualKeyCode::Comma => -0.2,
+                            _ => 0.2,
+                        };
+                        explode_growth = match state {
+                            ElementState::Pressed => growth,
+                            ElementState::Released => 0.0,
+                        };
+                        return;
+                    }
+                    // Morph towards the other shape or reverse a running morph
+                    VirtualKeyCode::P => {
+                        if state == ElementState::Pressed {
+                            morph_speed = if morph_speed != 0.0 {
+                                -morph_speed
+                            } else if morph == 1.0 {
+                                -0.5
+                            } else {
+                                0.5
+                            };
+                        }
+                        return;
+                    }
+                    VirtualKeyCode::C => {
+                        if state == ElementState::Pressed {
+                            if clip_supported {
+                                clip_enabled = !clip_enabled;
+                                println!("clip plane: {}", clip_enabled);
+                            } else {
+                                println!("the clip plane needs the shader_clip_distance feature");
+                            }
+                        }
+                        return;
+                    }
+                    // Move the clip plane along its normal or rotate it
+                    VirtualKeyCode::T
+                    | VirtualKeyCode::G
+                    | VirtualKeyCode::Y
+                    | VirtualKeyCode::H => {
+                        let speed = match state {
+                            ElementState::Pressed => 1.0,
+                            ElementState::Released => 0.0,
+                        };
+                        match key_code {
+                            VirtualKeyCode::T => clip_offset_speed = speed,
+                            VirtualKeyCode::G => clip_offset_speed = -speed,
+                            VirtualKeyCode::Y => clip_angle_speed = Rad(speed),
+                            _ => clip_angle_speed = Rad(-speed),
+                        }
+                        return;
+                    }
+                    VirtualKeyCode::Left
+                    | VirtualKeyCode::Right
+                    | VirtualKeyCode::Up
+                    | VirtualKeyCode::Down => {
+                        let speed = match state {
+                            ElementState::Pressed => 1.0,
+                            ElementState::Released => 0.0,
+                        };
+                        match key_code {
+                            VirtualKeyCode::Left => light_azimuth_speed = -speed,
+                            VirtualKeyCode::Right => light_azimuth_speed = speed,
+                            VirtualKeyCode::Up => light_elevation_speed = speed,
+                            _ => light_elevation_speed = -speed,
+                        }
+                        if state == ElementState::Released {
+                            if let Some((azimuth, elevation)) = lights.primary_angles() {
+                                println!(
+                                    "light azimuth: {:.1}°, elevation: {:.1}°",
+                                    azimuth.0, elevation.0
+                                );
+                            }
+                        }
+                        return;
+                    }
+                    VirtualKeyCode::F => {
+                        if state == ElementState::Pressed {
+                            if wireframe_pipeline.is_some() {
+                                wireframe = !wireframe;
+                                println!("wireframe: {}", wireframe);
+                            } else {
+                                println!("wireframe: not supported by the device");
+                            }
+                        }
+                        return;
+                    }
+                    VirtualKeyCode::M => {
+                        if state == ElementState::Pressed {
+                            glass = !glass;
+                            println!("glass: {}", glass);
+                        }
+                        return;
+                    }
+                    VirtualKeyCode::R => {
+                        if state == ElementState::Pressed {
+                            rotation_paused = !rotation_paused;
+                            println!("rotation paused: {}", rotation_paused);
+                        }
+                        return;
+                    }
+                    VirtualKeyCode::J => {
+                        if state == ElementState::Pressed {
+                            if samples > 1 {
+                                warn!("depth shots need --msaa 1");
+                            } else {
+                                take_depth_shot = true;
+                            }
+                        }
+                        return;
+                    }
+                    VirtualKeyCode::Z => {
+                        if state == ElementState::Pressed {
+                            show_heatmap = match show_heatmap {
+                                Some(Heatmap::Area) => None,
+                                _ => Some(Heatmap::Area),
+                            };
+                            println!("area heat map: {}", show_heatmap.is_some());
+                        }
+                        return;
+                    }
+                    // Off, the original index order, the optimized index order
+                    VirtualKeyCode::I => {
+                        if state == ElementState::Pressed {
+                            show_heatmap = match show_heatmap {
+                                Some(Heatmap::IndexOrder { optimized: false }) => {
+                                    Some(Heatmap::IndexOrder { optimized: true })
+                                }
+                                Some(Heatmap::IndexOrder { optimized: true }) => None,
+                                _ => Some(Heatmap::IndexOrder { optimized: false }),
+                            };
+                            match show_heatmap {
+                                Some(Heatmap::IndexOrder { optimized }) => println!(
+                                    "index order heat map: {}",
+                                    if optimized { "optimized" } else { "original" }
+                                ),
+                                _ => println!("index order heat map: false"),
+                            }
+                        }
+                        return;
+                    }
+                    VirtualKeyCode::O => {
+                        if state == ElementState::Pressed {
+                            look_at_origin = !look_at_origin;
+                            println!("look at origin: {}", look_at_origin);
+                        }
+                        return;
+                    }
+                    VirtualKeyCode::X => {
+                        if state == ElementState::Pressed {
+                            walk_mode = !walk_mode;
+                            println!("walk mode: {}", walk_mode);
+                        }
+                        return;
+                    }
+                    VirtualKeyCode::V => {
+                        if state == ElementState::Pressed {
+                            movement.smooth = !movement.smooth;
+                            println!("smooth movement: {}", movement.smooth);
+                        }
+                        return;
+                    }
+                    VirtualKeyCode::W => Vector3::unit_z(),
+                    VirtualKeyCode::A => -Vector3::unit_x(),
+                    VirtualKeyCode::S => -Vector3::unit_z(),
+                    VirtualKeyCode::D => Vector3::unit_x(),
+                    VirtualKeyCode::E => Vector3::unit_y(),
+                    VirtualKeyCode::Q => -Vector3::unit_y(),
                     _ => return,
                 };
                 match state {
-                    ElementState::Pressed => delta += d,
-                    ElementState::Released => delta -= d,
+                    ElementState::Pressed => direction += d,
+                    ElementState::Released => direction -= d,
                 }
             }
             Event::DeviceEvent {
                 event: DeviceEvent::MouseMotion { delta },
                 ..
             } => {
-                let d = Quaternion::from(Euler {
-                    x: Deg(0.1 * delta.1 as f32),
-                    y: Deg(0.1 * delta.0 as f32),
-                    z: Deg(0.0),
-                });
-                rotation = rotation * d;
-                // let euler = Euler::from(rotation);
-                // d = Quaternion::from(Euler {
-                //     x: Deg(0.0),
-                //     y: Deg(0.0),
-                //     z: (-euler.z).into(),
-                // });
-                // rotation = rotation * d;
+                // The cursor moves freely over other windows while it's released
+                if cursor_grabbed && focused {
+                    camera.apply_mouse(delta.0 as f32, delta.1 as f32);
+                }
             }
             e => warn!("{:?}", e),
         });
-        if done {
+        let finished = config
+            .frames
+            .map_or(false, |frames| frame_times.count() >= frames);
+        if done || finished || stress_done {
+            // Destroying the future blocks until the GPU is idle
+            mem::drop(previous_frame_end);
+            if finished {
+                println!("{}", frame_times);
+            }
+            if let Some((ref stress, _)) = stress {
+                println!("{}", stress.summary());
+            }
+            if let Some(ref mut csv) = timing_csv {
+                csv.flush().expect("failed to write timing CSV");
+            }
+            // The look sensitivity changed with Ctrl+[ and Ctrl+] is kept for the next start
+            if let Some(ref path) = config.file {
+                if camera.sensitivity != config.look_sensitivity {
+                    let value = camera.sensitivity.to_string();
+                    if let Err(err) = Config::save_setting(path, "look_sensitivity", &value) {
+                        warn!("failed to save the look sensitivity: {}", err);
+                    }
+                }
+            }
             // Keep debug_callback alive until here
-            std::mem::drop(debug_callback);
+            mem::drop(debug_callback);
+            return Ok(());
+        }
+    }
+}
+
+/// Runs the setup step `setup` with `safe == false` unless `safe_mode` has already been entered.
+///
+/// If that fails, safe mode is entered and the step is retried once with `safe == true`, in which
+/// case `setup` should only use the minimal configuration that's guaranteed to be supported.
+fn or_safe_mode<T, E: Debug>(
+    what: &str,
+    safe_mode: &mut bool,
+    mut setup: impl FnMut(bool) -> Result<T, E>,
+) -> Result<T, E> {
+    if !*safe_mode {
+        match setup(false) {
+            Ok(result) => return Ok(result),
+            Err(err) => {
+                warn!("failed to create {}: {:?}", what, err);
+                warn!("entering safe mode");
+                *safe_mode = true;
+            }
+        }
+    }
+    setup(true)
+}
+
+fn full_viewport(dimensions: [u32; 2]) -> DynamicState {
+    DynamicState {
+        line_width: None,
+        viewports: Some(vec![Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+            depth_range: 0.0..1.0,
+        }]),
+        scissors: None,
+    }
+}
+
+fn present_mode_supported(modes: &SupportedPresentModes, mode: PresentMode) -> bool {
+    match mode {
+        PresentMode::Immediate => modes.immediate,
+        PresentMode::Mailbox => modes.mailbox,
+        PresentMode::Fifo => modes.fifo,
+        PresentMode::Relaxed => modes.relaxed,
+    }
+}
+
+/// Writes the `depth` buffer of a frame with `dimensions` drawn by `camera` to `path` as a
+/// grayscale PNG, with the distances from the camera mapped linearly from white at the nearest to
+/// black at the farthest fragment. Pixels without a fragment are black as well. Returns the
+/// distances of the nearest and the farthest fragment, if there are any.
+fn write_depth_shot(
+    depth: &[u16],
+    dimensions: [u32; 2],
+    camera: &Camera,
+    path: &Path,
+) -> Result<Option<(f32, f32)>, String> {
+    // The depth buffer is cleared to 1
+    let distances: Vec<Option<f32>> = depth
+        .iter()
+        .map(|&depth| {
+            if depth < u16::max_value() {
+                Some(camera.linear_depth(f32::from(depth) / f32::from(u16::max_value())))
+            } else {
+                None
+            }
+        })
+        .collect();
+    let (nearest, farthest) = distances.iter().filter_map(|&distance| distance).fold(
+        (std::f32::INFINITY, 0.0f32),
+        |(nearest, farthest), distance| (nearest.min(distance), farthest.max(distance)),
+    );
+    let range = (farthest - nearest).max(std::f32::EPSILON);
+    let pixels: Vec<u8> = distances
+        .iter()
+        .map(|&distance| match distance {
+            Some(distance) => (255.0 * (1.0 - (distance - nearest) / range)).round() as u8,
+            None => 0,
+        })
+        .collect();
+
+    let file = File::create(path)
+        .map_err(|err| format!("failed to create {}: {}", path.display(), err))?;
+    write_grayscale_png(dimensions[0], dimensions[1], &pixels, BufWriter::new(file))
+        .map_err(|err| format!("failed to write {}: {}", path.display(), err))?;
+    Ok(if nearest <= farthest {
+        Some((nearest, farthest))
+    } else {
+        None
+    })
+}
+
+/// Prints the problems found by `--validate`, with the first few of each kind.
+fn print_report(report: &Report) {
+    fn print_problems<T: Debug>(what: &str, items: &[T]) {
+        const SHOWN: usize = 5;
+        if items.is_empty() {
             return;
         }
+        let mut shown = items
+            .iter()
+            .take(SHOWN)
+            .map(|item| format!("{:?}", item))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if items.len() > SHOWN {
+            shown.push_str(", ...");
+        }
+        println!("{} {}: {}", items.len(), what, shown);
+    }
+
+    println!(
+        "{} vertices, {} triangles",
+        report.vertices, report.triangles
+    );
+    match report.bounds {
+        Some(aabb) => println!("Bounds: {:?} to {:?}", aabb.min, aabb.max),
+        None => println!("Bounds: none"),
     }
+    if report.trailing_indices > 0 {
+        println!(
+            "{} indices after the last triangle",
+            report.trailing_indices
+        );
+    }
+    print_problems(
+        "triangles with indices out of range",
+        &report.out_of_range_triangles,
+    );
+    print_problems("NaN or infinite vertices", &report.invalid_vertices);
+    print_problems("degenerate triangles", &report.degenerate_triangles);
+    print_problems("non-manifold edges", &report.non_manifold_edges);
+    if report.boundary_edges > 0 {
+        println!("{} boundary edges; the mesh is open", report.boundary_edges);
+    }
+    if report.is_valid() {
+        println!("No problems found");
+    }
+}
+
+/// Logs the queue families of `physical` and the other available devices, to diagnose why none
+/// of them can be used to draw to `surface`.
+fn log_queue_families(physical: PhysicalDevice, surface: &Surface<Window>) {
+    error!("queue families of {}:", physical.name());
+    for family in physical.queue_families() {
+        let present = match surface.is_supported(family) {
+            Ok(supported) => supported.to_string(),
+            Err(err) => format!("{:?}", err),
+        };
+        error!(
+            "  #{}: {} queue(s), graphics: {}, compute: {}, transfers: {}, present: {}",
+            family.id(),
+            family.queues_count(),
+            family.supports_graphics(),
+            family.supports_compute(),
+            family.supports_transfers(),
+            present
+        );
+    }
+    let others = PhysicalDevice::enumerate(physical.instance())
+        .filter(|other| other.index() != physical.index())
+        .map(|other| format!("{} ({:?})", other.name(), other.ty()))
+        .collect::<Vec<_>>();
+    if others.is_empty() {
+        error!("no other devices available");
+    } else {
+        error!("other available devices: {}", others.join(", "));
+    }
+}
+
+/// Confines the cursor to the window and hides it, or releases it again.
+fn set_cursor_grab(window: &Window, grab: bool) {
+    if let Err(err) = window.grab_cursor(grab) {
+        warn!(
+            "failed to {} the cursor: {}",
+            if grab { "grab" } else { "release" },
+            err
+        );
+    }
+    window.hide_cursor(grab);
+}
+
+/// The size the swapchain images need to have: the surface's current extent, or if the surface
+/// leaves that to the application, the size of the window within the supported extents.
+fn swapchain_extent(caps: &Capabilities, window: &Window) -> [u32; 2] {
+    caps.current_extent.unwrap_or_else(|| {
+        let extent = window_extent(window);
+        let clamp = |i: usize| {
+            extent[i]
+                .max(caps.min_image_extent[i])
+                .min(caps.max_image_extent[i])
+        };
+        [clamp(0), clamp(1)]
+    })
+}
+
+/// The size of the window's client area in physical pixels.
+fn window_extent(window: &Window) -> [u32; 2] {
+    let size = window
+        .get_inner_size()
+        .expect("window no longer exists")
+        .to_physical(window.get_hidpi_factor());
+    [size.width.round() as u32, size.height.round() as u32]
+}
+
+fn duration_secs(duration: Duration) -> f32 {
+    duration.as_secs() as f32 + duration.subsec_nanos() as f32 * 1e-9
 }
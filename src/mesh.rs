@@ -0,0 +1,264 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::bounds::bounding_box;
+use crate::vertex::Vertex;
+
+/// Owned vertex and index data of a triangle mesh.
+///
+/// The data can be borrowed without copying, either typed or as raw bytes, e.g. to upload it to
+/// a GPU buffer of a different rendering abstraction. See [`Vertex`] for the vertex layout.
+/// Indices are `u32`s with three consecutive indices forming a triangle, which is
+/// counter-clockwise when seen from the outside in the object space of the vertices like those of
+/// the [`icosphere`](crate::icosphere::icosphere).
+#[derive(Debug, Clone)]
+pub struct Mesh {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Mesh {
+        Mesh { vertices, indices }
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    /// The vertex data as tightly packed bytes with a stride of [`Vertex::STRIDE`].
+    pub fn vertex_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.vertices)
+    }
+
+    /// The index data as bytes in native endianness; each index is 4 bytes.
+    pub fn index_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.indices)
+    }
+
+    pub fn into_parts(self) -> (Vec<Vertex>, Vec<u32>) {
+        (self.vertices, self.indices)
+    }
+}
+
+impl From<(Vec<Vertex>, Vec<u32>)> for Mesh {
+    fn from((vertices, indices): (Vec<Vertex>, Vec<u32>)) -> Mesh {
+        Mesh::new(vertices, indices)
+    }
+}
+
+/// Index data in the smallest type that can address every vertex of the mesh, to save memory and
+/// bandwidth on the GPU.
+///
+/// Icospheres up to subdivision level 6 (40 962 vertices) get 16-bit indices.
+#[derive(Debug, Clone)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    /// Converts `indices` to `u16` if a mesh with `num_vertices` vertices can be indexed by them.
+    pub fn compact(indices: Vec<u32>, num_vertices: usize) -> Indices {
+        debug_assert!(indices.iter().all(|&i| (i as usize) < num_vertices));
+        if num_vertices <= usize::from(u16::max_value()) {
+            Indices::U16(indices.into_iter().map(|i| i as u16).collect())
+        } else {
+            Indices::U32(indices)
+        }
+    }
+
+    pub fn num_indices(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+}
+
+/// The area of every triangle, in index buffer order.
+pub fn triangle_areas(vertices: &[Vertex], indices: &[u32]) -> Vec<f32> {
+    indices
+        .chunks(3)
+        .map(|face| {
+            let position = |i: u32| Vector3::from(vertices[i as usize].position());
+            let (a, b, c) = (position(face[0]), position(face[1]), position(face[2]));
+            0.5 * (b - a).cross(c - a).magnitude()
+        })
+        .collect()
+}
+
+/// Sets the normal of every vertex to the area-weighted average of the normals of the
+/// triangles around it, e.g. for meshes that come without normals. Vertices which aren't part
+/// of any triangle get a zero normal.
+///
+/// Like in [`explode`](crate::explode::explode), the triangles are expected to be
+/// counter-clockwise when seen from the outside.
+pub fn compute_normals(vertices: &mut [Vertex], indices: &[u32]) {
+    let mut normals = vec![Vector3::new(0.0, 0.0, 0.0); vertices.len()];
+    for face in indices.chunks(3) {
+        let position = |i: u32| Vector3::from(vertices[i as usize].position());
+        let (a, b, c) = (position(face[0]), position(face[1]), position(face[2]));
+        // The magnitude is twice the area of the triangle
+        let normal = (b - a).cross(c - a);
+        for &i in face {
+            normals[i as usize] += normal;
+        }
+    }
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        let normal = if normal.magnitude2() > 0.0 {
+            normal.normalize()
+        } else {
+            normal
+        };
+        vertex.set_normal(normal.into());
+    }
+}
+
+/// Reverses the winding of every triangle by swapping its second and third index.
+pub fn flip_winding(indices: &mut [u32]) {
+    for face in indices.chunks_mut(3) {
+        face.swap(1, 2);
+    }
+}
+
+/// Whether most triangles are clockwise when seen from the outside, i.e. their normals point
+/// towards the center of the bounding box rather than away from it, so that the winding should be
+/// flipped with [`flip_winding`].
+///
+/// This only works for roughly convex, closed meshes; triangles in the plane of the center are
+/// counted for neither side.
+pub fn faces_inward(vertices: &[Vertex], indices: &[u32]) -> bool {
+    let center = match bounding_box(vertices) {
+        Some(aabb) => aabb.center(),
+        None => return false,
+    };
+    let mut inward = 0;
+    let mut outward = 0;
+    for face in indices.chunks(3) {
+        let position = |i: u32| Vector3::from(vertices[i as usize].position());
+        let (a, b, c) = (position(face[0]), position(face[1]), position(face[2]));
+        let facing = (b - a).cross(c - a).dot((a + b + c) / 3.0 - center);
+        if facing < 0.0 {
+            inward += 1;
+        } else if facing > 0.0 {
+            outward += 1;
+        }
+    }
+    inward > outward
+}
+
+/// Number of quantization steps per unit used by [`mesh_hash`].
+const HASH_QUANTIZATION: f32 = 65536.;
+
+/// Computes a hash of the geometry which is stable across runs, platforms and Rust versions.
+///
+/// Every position coordinate is rounded to the nearest multiple of 1/65536 before hashing, so that
+/// differences in the last bits of the floating point results (e.g. due to fused multiply-add or
+/// different `sqrt` implementations) don't change the hash. Only values that happen to lie almost
+/// exactly between two quantization steps can still hash differently.
+///
+/// The hash itself is 64-bit FNV-1a over the little-endian bytes of the vertex count, the quantized
+/// coordinates, the index count and the indices.
+pub fn mesh_hash(vertices: &[Vertex], indices: &[u32]) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.write_u64(vertices.len() as u64);
+    for vertex in vertices {
+        for &coord in &vertex.position() {
+            hasher.write_u32((coord * HASH_QUANTIZATION).round() as i32 as u32);
+        }
+    }
+    hasher.write_u64(indices.len() as u64);
+    for &index in indices {
+        hasher.write_u32(index);
+    }
+    hasher.finish()
+}
+
+/// 64-bit FNV-1a; unlike `DefaultHasher` its output is specified and will never change.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write_u8(&mut self, byte: u8) {
+        self.0 ^= u64::from(byte);
+        self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        for i in 0..4 {
+            self.write_u8((value >> (i * 8)) as u8);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        for i in 0..8 {
+            self.write_u8((value >> (i * 8)) as u8);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::icosphere::icosphere;
+
+    /// Fails when the icosphere generation changes. If the change is intended, update the hashes.
+    #[test]
+    fn icosphere_hashes_are_unchanged() {
+        let expected = [
+            0x738c_20de_d21e_8241,
+            0x4942_a4e8_db34_2b5a,
+            0xa78e_9f5e_8804_3923,
+            0x08de_421f_d8a3_f595,
+            0x183c_6f0f_0218_925c,
+        ];
+        for (level, &hash) in expected.iter().enumerate() {
+            let (vertices, indices) = icosphere(level as u8);
+            assert_eq!(mesh_hash(&vertices, &indices), hash, "level {}", level);
+        }
+    }
+
+    #[test]
+    fn flipped_icosphere_faces_inward() {
+        let (vertices, mut indices) = icosphere(2);
+        assert!(!faces_inward(&vertices, &indices));
+        flip_winding(&mut indices);
+        assert!(faces_inward(&vertices, &indices));
+    }
+
+    #[test]
+    fn compact_indices_switch_to_u32_above_u16_max() {
+        let (vertices, indices) = icosphere(6);
+        assert_eq!(vertices.len(), 40_962);
+        assert!(matches!(
+            Indices::compact(indices, vertices.len()),
+            Indices::U16(_)
+        ));
+        let (vertices, indices) = icosphere(7);
+        assert!(matches!(
+            Indices::compact(indices, vertices.len()),
+            Indices::U32(_)
+        ));
+
+        // The largest index of 65 536 vertices doesn't fit into a u16 anymore
+        assert!(matches!(
+            Indices::compact(vec![0, 1, 65_534], 65_535),
+            Indices::U16(_)
+        ));
+        assert!(matches!(
+            Indices::compact(vec![0, 1, 65_535], 65_536),
+            Indices::U32(_)
+        ));
+    }
+}
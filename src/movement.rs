@@ -0,0 +1,63 @@
+use cgmath::{InnerSpace, Quaternion, Vector3, Zero};
+
+/// Camera movement that either snaps to the velocity requested by the pressed keys or, if
+/// `smooth` is set, accelerates towards it and smoothly comes to a halt when the keys are
+/// released.
+pub struct Movement {
+    /// Current velocity in units per second (in camera space).
+    velocity: Vector3<f32>,
+    /// Whether to accelerate/damp the velocity instead of snapping to the target velocity.
+    pub smooth: bool,
+    /// Maximum change of velocity in units per second².
+    pub acceleration: f32,
+    /// Exponential decay rate of the velocity per second when no key is pressed.
+    pub damping: f32,
+}
+
+impl Movement {
+    pub fn new(smooth: bool, acceleration: f32, damping: f32) -> Movement {
+        Movement {
+            velocity: Vector3::zero(),
+            smooth,
+            acceleration,
+            damping,
+        }
+    }
+
+    /// Updates the velocity towards `target` and returns the displacement for a frame which
+    /// took `dt` seconds.
+    pub fn update(&mut self, target: Vector3<f32>, dt: f32) -> Vector3<f32> {
+        if !self.smooth {
+            self.velocity = target;
+        } else if target.is_zero() {
+            self.velocity *= (-self.damping * dt).exp();
+        } else {
+            let diff = target - self.velocity;
+            let max_change = self.acceleration * dt;
+            if diff.magnitude() <= max_change {
+                self.velocity = target;
+            } else {
+                self.velocity += diff.normalize_to(max_change);
+            }
+        }
+        self.velocity * dt
+    }
+}
+
+/// Turns a camera space `displacement` into a world space one that keeps the altitude when moving
+/// forwards or sideways, no matter how the camera with `rotation` is tilted. The y component
+/// moves straight along the world's y axis instead.
+pub fn walk(rotation: Quaternion<f32>, displacement: Vector3<f32>) -> Vector3<f32> {
+    let horizontal = |v: Vector3<f32>| {
+        let v = Vector3::new(v.x, 0.0, v.z);
+        if v.magnitude2() > 1e-6 {
+            v.normalize()
+        } else {
+            // Looking straight up or down
+            Vector3::zero()
+        }
+    };
+    let forward = horizontal(rotation * Vector3::unit_z());
+    let right = horizontal(rotation * Vector3::unit_x());
+    forward * displacement.z + right * displacement.x + Vector3::unit_y() * displacement.y
+}
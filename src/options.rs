@@ -0,0 +1,250 @@
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+use vulkano::swapchain::{CompositeAlpha, PresentMode};
+
+use curveball::icosphere::MAX_SUBDIVISION_LEVEL;
+
+use crate::instances::InstanceColors;
+use crate::texture::TEXTURE_SIZE;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "curveball",
+    after_help = "Defaults for the options can also be set with CURVEBALL_* environment variables \
+                  or in a config file, see --config."
+)]
+pub struct Options {
+    /// How the window's alpha channel is composited: opaque, premultiplied, postmultiplied or
+    /// inherit. Defaults to opaque if the surface supports it.
+    #[structopt(long = "alpha", parse(try_from_str = "parse_composite_alpha"))]
+    pub alpha: Option<CompositeAlpha>,
+
+    /// Print statistics about the generated mesh and detailed rendering statistics every second.
+    /// The times are measured on the CPU; the GPU execution time isn't measured
+    #[structopt(long = "stats")]
+    pub stats: bool,
+
+    /// Subdivision level of the most detailed icosphere, up to 10 [default: 6]. The other levels
+    /// of detail are subdivided 2 and 4 times less
+    #[structopt(long = "subdivisions", parse(try_from_str = "parse_subdivisions"))]
+    pub subdivisions: Option<u8>,
+
+    /// Draw a cube of N×N×N copies of the mesh, up to 64 [default: 1]. Every copy gets the level
+    /// of detail for its own distance, except while the exploded view, a heat map, the morph or
+    /// --stress is shown
+    #[structopt(long = "grid", parse(try_from_str = "parse_grid"))]
+    pub grid: Option<u32>,
+
+    /// Tint the copies of --grid: white (the color of the mesh), position (red, green and blue
+    /// grow along the x, y and z axes of the grid) or random[:SEED], with the same colors for the
+    /// same seed [default: white, seed 0]
+    #[structopt(
+        long = "instance-colors",
+        parse(try_from_str = "parse_instance_colors")
+    )]
+    pub instance_colors: Option<InstanceColors>,
+
+    /// Draw the meshes with a checkerboard texture instead of the colors of their texture
+    /// coordinates, which shows how the texture is stretched and where its seams are
+    #[structopt(long = "uv-test")]
+    pub uv_test: bool,
+
+    /// Number of squares of the --uv-test checkerboard along each texture coordinate, up to 512
+    /// [default: 8]
+    #[structopt(long = "uv-test-tiles", parse(try_from_str = "parse_uv_test_tiles"))]
+    pub uv_test_tiles: Option<u32>,
+
+    /// The two colors of the --uv-test checkerboard as hexadecimal sRGB colors separated by a
+    /// comma [default: ffffff,303030]
+    #[structopt(long = "uv-test-colors", parse(try_from_str = "parse_uv_test_colors"))]
+    pub uv_test_colors: Option<[[u8; 3]; 2]>,
+
+    /// How frames are presented: fifo (vsync), mailbox or immediate. Falls back to fifo if the
+    /// surface doesn't support it [default: mailbox if supported, otherwise fifo]
+    #[structopt(long = "present-mode", parse(try_from_str = "parse_present_mode"))]
+    pub present_mode: Option<PresentMode>,
+
+    /// Samples per pixel for multisample anti-aliasing: 1 (off), 2, 4 or 8 [default: 1]. Lowered to
+    /// the highest count the device supports
+    #[structopt(long = "msaa", parse(try_from_str = "parse_msaa"))]
+    pub msaa: Option<u32>,
+
+    /// Number of Laplacian smoothing iterations applied to the mesh
+    #[structopt(long = "smooth")]
+    pub smooth: Option<u32>,
+
+    /// Exit after rendering this many frames and print a frame time summary
+    #[structopt(long = "frames")]
+    pub frames: Option<u32>,
+
+    /// Render icospheres with increasing subdivision levels until the frame rate drops below 30
+    /// fps, then exit and print the highest triangle throughput
+    #[structopt(long = "stress")]
+    pub stress: bool,
+
+    /// Write the frame time, command buffer recording time and triangle count of every frame to
+    /// this CSV file
+    #[structopt(long = "timing-csv", parse(from_os_str))]
+    pub timing_csv: Option<PathBuf>,
+
+    /// Render the first mesh of this .gltf or .glb file instead of the icosphere
+    #[structopt(long = "gltf", parse(from_os_str))]
+    pub gltf: Option<PathBuf>,
+
+    /// Reverse the winding of the triangles of the --gltf file, for files with clockwise
+    /// triangles. Without it the winding is only reversed if most triangles face inwards
+    #[structopt(long = "flip-winding")]
+    pub flip_winding: bool,
+
+    /// Mouse look sensitivity in degrees per pixel [default: 0.1]. Can be changed at runtime
+    /// with Ctrl+[ and Ctrl+]
+    #[structopt(long = "look-sensitivity")]
+    pub look_sensitivity: Option<f32>,
+
+    /// Camera movement speed in units per second [default: 3]
+    #[structopt(long = "move-speed")]
+    pub move_speed: Option<f32>,
+
+    /// How fast the camera speeds up to the movement speed, in units per second² [default: 20]
+    #[structopt(long = "acceleration")]
+    pub acceleration: Option<f32>,
+
+    /// How fast the camera comes to a halt when no movement key is pressed, as the exponential
+    /// decay rate of its velocity per second [default: 8]
+    #[structopt(long = "damping")]
+    pub damping: Option<f32>,
+
+    /// Accelerate and damp the camera movement with --acceleration and --damping instead of
+    /// starting and stopping instantly. Can be toggled at runtime with V
+    #[structopt(long = "smooth-movement")]
+    pub smooth_movement: bool,
+
+    /// Index of the device to render with, as listed at startup [default: the first discrete GPU
+    /// that can present to the window]
+    #[structopt(long = "gpu")]
+    pub gpu: Option<usize>,
+
+    /// Compute the normals of the morphing mesh (see P) in a compute shader instead of on the
+    /// CPU. Ignored if the graphics queue doesn't support compute
+    #[structopt(long = "gpu-normals")]
+    pub gpu_normals: bool,
+
+    /// Read settings from this file, with a `name = value` pair per line; the names are those of
+    /// the CURVEBALL_* environment variables without the prefix in lowercase. Command line
+    /// options override the file, which overrides the environment. A look sensitivity changed at
+    /// runtime is saved to the file on exit
+    #[structopt(long = "config", parse(from_os_str))]
+    pub config: Option<PathBuf>,
+
+    /// Write the icosphere to this Wavefront OBJ file and exit without opening a window
+    #[structopt(long = "export", parse(from_os_str))]
+    pub export: Option<PathBuf>,
+
+    /// Write the depth buffer of the first frame to this PNG file, in grayscale from white at the
+    /// nearest to black at the farthest fragment. J writes the depth buffer of the current frame
+    /// to it [default: depth.png]. Needs --msaa 1
+    #[structopt(long = "depth-shot", parse(from_os_str))]
+    pub depth_shot: Option<PathBuf>,
+
+    /// Check this .obj, .gltf or .glb file for indices out of range, NaN or infinite vertices,
+    /// degenerate triangles and non-manifold edges, print what was found and exit without opening
+    /// a window. The exit code is 1 if there were any problems
+    #[structopt(long = "validate", parse(from_os_str))]
+    pub validate: Option<PathBuf>,
+}
+
+pub fn parse_composite_alpha(s: &str) -> Result<CompositeAlpha, String> {
+    match s {
+        "opaque" => Ok(CompositeAlpha::Opaque),
+        "premultiplied" => Ok(CompositeAlpha::PreMultiplied),
+        "postmultiplied" => Ok(CompositeAlpha::PostMultiplied),
+        "inherit" => Ok(CompositeAlpha::Inherit),
+        _ => Err(format!("unknown composite alpha mode: {}", s)),
+    }
+}
+
+pub fn parse_subdivisions(s: &str) -> Result<u8, String> {
+    let level = s.parse::<u8>().map_err(|e| e.to_string())?;
+    if level > MAX_SUBDIVISION_LEVEL {
+        return Err(format!(
+            "subdivision level {} is too high; the maximum is {}",
+            level, MAX_SUBDIVISION_LEVEL
+        ));
+    }
+    Ok(level)
+}
+
+pub fn parse_grid(s: &str) -> Result<u32, String> {
+    let n = s.parse::<u32>().map_err(|e| e.to_string())?;
+    if n == 0 || n > 64 {
+        return Err(format!("grid size {} is not between 1 and 64", n));
+    }
+    Ok(n)
+}
+
+pub fn parse_instance_colors(s: &str) -> Result<InstanceColors, String> {
+    match s {
+        "white" => Ok(InstanceColors::White),
+        "position" => Ok(InstanceColors::Position),
+        "random" => Ok(InstanceColors::Random(0)),
+        _ if s.starts_with("random:") => {
+            let seed = s["random:".len()..]
+                .parse()
+                .map_err(|e| format!("invalid seed: {}", e))?;
+            Ok(InstanceColors::Random(seed))
+        }
+        _ => Err(format!("unknown instance colors: {}", s)),
+    }
+}
+
+pub fn parse_uv_test_tiles(s: &str) -> Result<u32, String> {
+    let tiles = s.parse::<u32>().map_err(|e| e.to_string())?;
+    if tiles == 0 || tiles > TEXTURE_SIZE {
+        return Err(format!(
+            "tile count {} is not between 1 and {}",
+            tiles, TEXTURE_SIZE
+        ));
+    }
+    Ok(tiles)
+}
+
+pub fn parse_uv_test_colors(s: &str) -> Result<[[u8; 3]; 2], String> {
+    let parse_color = |color: &str| {
+        let color = color.trim().trim_start_matches('#');
+        if color.len() != 6 || !color.is_ascii() {
+            return Err(format!("{} is not a color like ff8000", color));
+        }
+        let mut rgb = [0; 3];
+        for (i, channel) in rgb.iter_mut().enumerate() {
+            *channel = u8::from_str_radix(&color[2 * i..2 * i + 2], 16)
+                .map_err(|_| format!("{} is not a color like ff8000", color))?;
+        }
+        Ok(rgb)
+    };
+    let mut colors = s.split(',');
+    match (colors.next(), colors.next(), colors.next()) {
+        (Some(first), Some(second), None) => Ok([parse_color(first)?, parse_color(second)?]),
+        _ => Err(format!(
+            "expected two colors separated by a comma, got {}",
+            s
+        )),
+    }
+}
+
+pub fn parse_msaa(s: &str) -> Result<u32, String> {
+    let samples = s.parse::<u32>().map_err(|e| e.to_string())?;
+    match samples {
+        1 | 2 | 4 | 8 => Ok(samples),
+        _ => Err(format!("sample count {} is not 1, 2, 4 or 8", samples)),
+    }
+}
+
+pub fn parse_present_mode(s: &str) -> Result<PresentMode, String> {
+    match s {
+        "fifo" => Ok(PresentMode::Fifo),
+        "mailbox" => Ok(PresentMode::Mailbox),
+        "immediate" => Ok(PresentMode::Immediate),
+        _ => Err(format!("unknown present mode: {}", s)),
+    }
+}
@@ -0,0 +1,113 @@
+use std::io::{self, Write};
+
+/// Largest amount of data in a stored deflate block.
+const MAX_STORED_BLOCK: usize = 65535;
+
+/// Writes an 8-bit grayscale image of `width`×`height` `pixels`, row by row from the top, as a PNG
+/// file.
+///
+/// The image data is stored in uncompressed deflate blocks, which any PNG decoder can read, so no
+/// compression library is needed. The files are about as large as the pixels.
+pub fn write_grayscale_png(
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+    mut writer: impl Write,
+) -> io::Result<()> {
+    assert_eq!(pixels.len(), width as usize * height as usize);
+
+    writer.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, grayscale, deflate, adaptive filtering, no interlacing
+    header.extend_from_slice(&[8, 0, 0, 0, 0]);
+    write_chunk(&mut writer, b"IHDR", &header)?;
+
+    // Every row starts with its filter type, which is 0 for none
+    let mut scanlines = Vec::with_capacity(pixels.len() + height as usize);
+    for row in pixels.chunks(width.max(1) as usize) {
+        scanlines.push(0);
+        scanlines.extend_from_slice(row);
+    }
+    // A zlib stream with the default window size and no compression
+    let mut data = vec![0x78, 0x01];
+    let mut blocks = scanlines.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        data.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        data.push(last as u8);
+        data.extend_from_slice(&len.to_le_bytes());
+        data.extend_from_slice(&(!len).to_le_bytes());
+        data.extend_from_slice(block);
+    }
+    data.extend_from_slice(&adler32(&scanlines).to_be_bytes());
+    write_chunk(&mut writer, b"IDAT", &data)?;
+
+    write_chunk(&mut writer, b"IEND", &[])?;
+    writer.flush()
+}
+
+fn write_chunk(writer: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    let crc = crc32(kind.iter().chain(data));
+    writer.write_all(&crc.to_be_bytes())
+}
+
+/// The CRC-32 of PNG chunks, computed bit by bit.
+fn crc32<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> u32 {
+    let mut crc = 0xffff_ffff;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// The checksum at the end of a zlib stream.
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_match_known_values() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn image_is_split_into_stored_blocks() {
+        let (width, height) = (300, 300);
+        let pixels: Vec<u8> = (0..width * height).map(|i| i as u8).collect();
+        let mut png = Vec::new();
+        write_grayscale_png(width, height, &pixels, &mut png).unwrap();
+
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR"));
+        assert!(png.ends_with(b"\0\0\0\0IEND\xae\x42\x60\x82"));
+        // The scanlines with their filter bytes need two blocks
+        let scanlines = (width * height + height) as usize;
+        let idat_len = 2 + 2 * 5 + scanlines + 4;
+        assert_eq!(png.len(), 8 + 25 + (12 + idat_len) + 12);
+    }
+}
@@ -5,3 +5,31 @@ pub mod vs {
 pub mod fs {
     vulkano_shaders::shader!{ ty: "fragment", path: "src/shaders/fragment.glsl"}
 }
+
+pub mod line_vs {
+    vulkano_shaders::shader!{ ty: "vertex", path: "src/shaders/line_vertex.glsl"}
+}
+
+pub mod line_fs {
+    vulkano_shaders::shader!{ ty: "fragment", path: "src/shaders/line_fragment.glsl"}
+}
+
+pub mod impostor_vs {
+    vulkano_shaders::shader!{ ty: "vertex", path: "src/shaders/impostor_vertex.glsl"}
+}
+
+pub mod impostor_fs {
+    vulkano_shaders::shader!{ ty: "fragment", path: "src/shaders/impostor_fragment.glsl"}
+}
+
+pub mod glass_fs {
+    vulkano_shaders::shader!{ ty: "fragment", path: "src/shaders/glass_fragment.glsl"}
+}
+
+pub mod vs_no_clip {
+    vulkano_shaders::shader!{ ty: "vertex", path: "src/shaders/vertex_no_clip.glsl"}
+}
+
+pub mod normals_cs {
+    vulkano_shaders::shader!{ ty: "compute", path: "src/shaders/normals.glsl"}
+}
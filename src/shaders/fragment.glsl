@@ -1,14 +1,48 @@
 #version 450
 
+// Must match `MAX_LIGHTS` in `lights.rs`
+#define MAX_LIGHTS 8
+
 layout(location = 0) in vec3 v_normal;
+layout(location = 1) in vec3 v_position;
+layout(location = 2) in vec2 v_uv;
+// Tint of the instance
+layout(location = 3) in vec3 v_color;
+// Linear; the sRGB swapchain image applies the gamma encoding when it's stored
 layout(location = 0) out vec4 f_color;
 
-const vec3 LIGHT = vec3(4.0, 4.0, 4.0);
+struct Light {
+    // w == 0: directional light with xyz pointing towards the light
+    // w == 1: point light at xyz
+    vec4 position;
+    // rgb: color, a: intensity
+    vec4 color;
+};
+
+layout(set = 0, binding = 1) uniform Lights {
+    Light lights[MAX_LIGHTS];
+    uint num_lights;
+} lighting;
+
+// The colors of the texture coordinates by default, or the checkerboard of --uv-test
+layout(set = 0, binding = 2) uniform texture2D base_texture;
+layout(set = 0, binding = 3) uniform sampler base_sampler;
+
+// Fraction of the base color that is lit regardless of the lights
+const float AMBIENT = 0.15;
 
 void main() {
-    float brightness = dot(normalize(v_normal), normalize(LIGHT));
-    vec3 dark_color = vec3(0.5, 0.0, 0.0);
-    vec3 regular_color = vec3(1.0, 0.0, 0.0);
+    vec3 normal = normalize(v_normal);
+    vec3 base_color = texture(sampler2D(base_texture, base_sampler), v_uv).rgb * v_color;
+
+    // Lambertian shading on top of a small ambient term that keeps the dark side visible
+    vec3 color = AMBIENT * base_color;
+    for (uint i = 0; i < lighting.num_lights; i++) {
+        Light light = lighting.lights[i];
+        vec3 to_light = light.position.xyz - v_position * light.position.w;
+        float diffuse = max(dot(normal, normalize(to_light)), 0.0);
+        color += base_color * light.color.rgb * light.color.a * diffuse;
+    }
 
-    f_color = vec4(mix(dark_color, regular_color, brightness), 1.0);
-}
\ No newline at end of file
+    f_color = vec4(clamp(color, 0.0, 1.0), 1.0);
+}
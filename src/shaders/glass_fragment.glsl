@@ -0,0 +1,53 @@
+#version 450
+
+// Must match `MAX_LIGHTS` in `lights.rs`
+#define MAX_LIGHTS 8
+
+layout(location = 0) in vec3 v_normal;
+layout(location = 1) in vec3 v_position;
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform Data {
+    mat4 world;
+    mat4 view;
+    mat4 proj;
+    vec4 clip_plane;
+} uniforms;
+
+struct Light {
+    // w == 0: directional light with xyz pointing towards the light
+    // w == 1: point light at xyz
+    vec4 position;
+    // rgb: color, a: intensity
+    vec4 color;
+};
+
+layout(set = 0, binding = 1) uniform Lights {
+    Light lights[MAX_LIGHTS];
+    uint num_lights;
+} lighting;
+
+// Opacity when looking straight at the surface and at grazing angles
+const float MIN_ALPHA = 0.1;
+const float MAX_ALPHA = 0.9;
+
+void main() {
+    // The inside of the sphere faces the other way
+    vec3 normal = normalize(v_normal) * (gl_FrontFacing ? 1.0 : -1.0);
+    // The rotation part of the view matrix is orthonormal so its transpose is its inverse
+    vec3 camera = -transpose(mat3(uniforms.view)) * uniforms.view[3].xyz;
+    vec3 to_camera = normalize(camera - v_position);
+
+    vec3 glass_color = vec3(0.6, 0.8, 1.0);
+    vec3 color = 0.2 * glass_color;
+    for (uint i = 0; i < lighting.num_lights; i++) {
+        Light light = lighting.lights[i];
+        vec3 to_light = light.position.xyz - v_position * light.position.w;
+        float diffuse = max(dot(normal, normalize(to_light)), 0.0);
+        color += glass_color * light.color.rgb * light.color.a * diffuse;
+    }
+
+    // Schlick's approximation of the Fresnel term makes the edges more opaque
+    float fresnel = pow(1.0 - max(dot(normal, to_camera), 0.0), 5.0);
+    f_color = vec4(color, mix(MIN_ALPHA, MAX_ALPHA, fresnel));
+}
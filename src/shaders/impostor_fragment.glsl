@@ -0,0 +1,81 @@
+#version 450
+
+layout(location = 0) in vec3 v_view_pos;
+layout(location = 1) flat in vec3 v_center;
+layout(location = 2) flat in vec3 v_world_center;
+layout(location = 3) flat in vec3 v_color;
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform Data {
+    // Only the rotation of the mesh, to map the texture the same way
+    mat4 world;
+    mat4 view;
+    mat4 proj;
+    float radius;
+} uniforms;
+
+// Must match `MAX_LIGHTS` in `lights.rs`
+#define MAX_LIGHTS 8
+
+struct Light {
+    // w == 0: directional light with xyz pointing towards the light
+    // w == 1: point light at xyz
+    vec4 position;
+    // rgb: color, a: intensity
+    vec4 color;
+};
+
+layout(set = 0, binding = 1) uniform Lights {
+    Light lights[MAX_LIGHTS];
+    uint num_lights;
+} lighting;
+
+// Same texture as the mesh
+layout(set = 0, binding = 2) uniform texture2D base_texture;
+layout(set = 0, binding = 3) uniform sampler base_sampler;
+
+// Fraction of the base color that is lit regardless of the lights
+const float AMBIENT = 0.15;
+
+const float PI = 3.14159265;
+
+// Must match `spherical_uv` in `icosphere.rs`
+vec2 spherical_uv(vec3 direction) {
+    float u = 0.5 + atan(direction.z, direction.x) / (2.0 * PI);
+    float v = 0.5 - asin(clamp(direction.y, -1.0, 1.0)) / PI;
+    return vec2(u, v);
+}
+
+void main() {
+    vec3 center = v_center;
+    float radius = uniforms.radius;
+
+    // Intersect the ray from the camera (the origin in view space) with the sphere
+    vec3 dir = normalize(v_view_pos);
+    float b = dot(dir, center);
+    float discriminant = b * b - dot(center, center) + radius * radius;
+    if (discriminant < 0.0) {
+        discard;
+    }
+    vec3 hit = dir * (b - sqrt(discriminant));
+
+    // The rotation part of the view matrix is orthonormal so its transpose is its inverse
+    vec3 normal = transpose(mat3(uniforms.view)) * normalize(hit - center);
+
+    vec3 position = v_world_center + normal * radius;
+
+    // Same as the mesh in fragment.glsl, with the texture coordinates of the unrotated sphere
+    vec2 uv = spherical_uv(transpose(mat3(uniforms.world)) * normal);
+    vec3 base_color = texture(sampler2D(base_texture, base_sampler), uv).rgb * v_color;
+
+    // Lambertian shading on top of a small ambient term that keeps the dark side visible
+    vec3 color = AMBIENT * base_color;
+    for (uint i = 0; i < lighting.num_lights; i++) {
+        Light light = lighting.lights[i];
+        vec3 to_light = light.position.xyz - position * light.position.w;
+        float diffuse = max(dot(normal, normalize(to_light)), 0.0);
+        color += base_color * light.color.rgb * light.color.a * diffuse;
+    }
+
+    f_color = vec4(clamp(color, 0.0, 1.0), 1.0);
+}
@@ -0,0 +1,40 @@
+#version 450
+
+layout(location = 0) in vec2 corner;
+// Per instance
+layout(location = 1) in vec3 translation;
+layout(location = 2) in vec3 color;
+layout(location = 0) out vec3 v_view_pos;
+// The center of the sphere in view and world space
+layout(location = 1) flat out vec3 v_center;
+layout(location = 2) flat out vec3 v_world_center;
+layout(location = 3) flat out vec3 v_color;
+
+layout(set = 0, binding = 0) uniform Data {
+    // Only the rotation of the mesh, to map the texture the same way
+    mat4 world;
+    mat4 view;
+    mat4 proj;
+    float radius;
+} uniforms;
+
+void main() {
+    vec3 center = (uniforms.view * vec4(translation, 1.0)).xyz;
+    float radius = uniforms.radius;
+    float dist = length(center);
+
+    // The quad is perpendicular to the direction towards the sphere so that the cone of rays
+    // touching the sphere intersects it in a circle, with a radius that's slightly larger than
+    // the sphere's itself.
+    vec3 dir = center / dist;
+    vec3 right = abs(dir.y) < 0.99 ? normalize(cross(dir, vec3(0.0, 1.0, 0.0))) : vec3(1.0, 0.0, 0.0);
+    vec3 up = cross(right, dir);
+    float size = radius * dist / sqrt(max(dist * dist - radius * radius, 1e-6));
+
+    vec2 offset = corner * size;
+    v_view_pos = center + offset.x * right + offset.y * up;
+    v_center = center;
+    v_world_center = translation;
+    v_color = color;
+    gl_Position = uniforms.proj * vec4(v_view_pos, 1.0);
+}
@@ -0,0 +1,19 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 color;
+// Per instance
+layout(location = 2) in vec3 translation;
+layout(location = 0) out vec3 v_color;
+
+layout(set = 0, binding = 0) uniform Data {
+    mat4 world;
+    mat4 view;
+    mat4 proj;
+} uniforms;
+
+void main() {
+    v_color = color;
+    vec3 world_position = (uniforms.world * vec4(position, 1.0)).xyz + translation;
+    gl_Position = uniforms.proj * uniforms.view * vec4(world_position, 1.0);
+}
@@ -0,0 +1,71 @@
+#version 450
+
+// Recomputes the normals of a deformed mesh, with an invocation per vertex that sets its normal
+// to the area-weighted average of the normals of the triangles around it like `compute_normals`
+// in `mesh.rs`. Must match `WORK_GROUP_SIZE` in `gpu_normals.rs`.
+layout(local_size_x = 64) in;
+
+// Floats per vertex and offsets of the attributes; must match `Vertex` in `vertex.rs`
+const uint STRIDE = 8;
+const uint POSITION = 0;
+const uint NORMAL = 3;
+
+layout(std430, set = 0, binding = 0) readonly buffer Source {
+    float vertices[];
+} src;
+
+layout(std430, set = 0, binding = 1) readonly buffer Indices {
+    uint indices[];
+};
+
+// The triangles around vertex i are triangles[offsets[i]] to triangles[offsets[i + 1] - 1]
+layout(std430, set = 0, binding = 2) readonly buffer Offsets {
+    uint offsets[];
+};
+
+layout(std430, set = 0, binding = 3) readonly buffer Triangles {
+    uint triangles[];
+};
+
+// The vertices of the source with the new normals
+layout(std430, set = 0, binding = 4) buffer Destination {
+    float vertices[];
+} dst;
+
+layout(push_constant) uniform PushConstants {
+    uint vertex_count;
+} constants;
+
+vec3 position(uint index) {
+    uint first = index * STRIDE + POSITION;
+    return vec3(src.vertices[first], src.vertices[first + 1], src.vertices[first + 2]);
+}
+
+void main() {
+    uint i = gl_GlobalInvocationID.x;
+    if (i >= constants.vertex_count) {
+        return;
+    }
+
+    vec3 normal = vec3(0.0);
+    for (uint t = offsets[i]; t < offsets[i + 1]; t++) {
+        uint first = triangles[t] * 3;
+        vec3 a = position(indices[first]);
+        vec3 b = position(indices[first + 1]);
+        vec3 c = position(indices[first + 2]);
+        // The magnitude is twice the area of the triangle
+        normal += cross(b - a, c - a);
+    }
+    // Vertices which aren't part of any triangle get a zero normal
+    if (dot(normal, normal) > 0.0) {
+        normal = normalize(normal);
+    }
+
+    // Copies the position and the texture coordinates along with the old normal
+    for (uint j = i * STRIDE; j < (i + 1) * STRIDE; j++) {
+        dst.vertices[j] = src.vertices[j];
+    }
+    dst.vertices[i * STRIDE + NORMAL] = normal.x;
+    dst.vertices[i * STRIDE + NORMAL + 1] = normal.y;
+    dst.vertices[i * STRIDE + NORMAL + 2] = normal.z;
+}
@@ -0,0 +1,32 @@
+#version 450
+
+// vertex.glsl without the clip plane, for devices without the shader_clip_distance feature. The
+// inputs and the uniform block have to stay the same as in vertex.glsl.
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 uv;
+// Per instance
+layout(location = 3) in vec3 translation;
+layout(location = 4) in vec3 color;
+layout(location = 0) out vec3 v_normal;
+layout(location = 1) out vec3 v_position;
+layout(location = 2) out vec2 v_uv;
+layout(location = 3) out vec3 v_color;
+
+layout(set = 0, binding = 0) uniform Data {
+    mat4 world;
+    mat4 view;
+    mat4 proj;
+    // Everything on the negative side of the plane dot(plane.xyz, p) + plane.w = 0 is cut away
+    vec4 clip_plane;
+} uniforms;
+
+void main() {
+    // The world matrix has no non-uniform scale, otherwise this would need the normal matrix
+    v_normal = mat3(uniforms.world) * normal;
+    v_position = (uniforms.world * vec4(position, 1.0)).xyz + translation;
+    v_uv = uv;
+    v_color = color;
+    gl_Position = uniforms.proj * uniforms.view * vec4(v_position, 1.0);
+}
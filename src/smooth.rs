@@ -0,0 +1,74 @@
+use cgmath::{Vector3, Zero};
+
+use crate::adjacency::Adjacency;
+use crate::vertex::Vertex;
+
+/// Moves every vertex `lambda` of the way towards the average position of its neighbors and
+/// repeats that `iterations` times.
+///
+/// `lambda` should lie in `0.0..=1.0`. Note that Laplacian smoothing also shrinks the mesh. The
+/// normals aren't changed; recompute them with [`compute_normals`](crate::mesh::compute_normals).
+pub fn laplacian_smooth(
+    vertices: &mut [Vertex],
+    adjacency: &Adjacency,
+    iterations: u32,
+    lambda: f32,
+) {
+    debug_assert_eq!(vertices.len(), adjacency.vertex_neighbors.len());
+
+    let mut positions: Vec<Vector3<f32>> = vertices.iter().map(|v| v.position().into()).collect();
+    let mut smoothed = positions.clone();
+    for _ in 0..iterations {
+        for (i, neighbors) in adjacency.vertex_neighbors.iter().enumerate() {
+            if neighbors.is_empty() {
+                continue;
+            }
+            let sum = neighbors
+                .iter()
+                .fold(Vector3::zero(), |sum, &n| sum + positions[n as usize]);
+            let average = sum / neighbors.len() as f32;
+            smoothed[i] = positions[i] + lambda * (average - positions[i]);
+        }
+        std::mem::swap(&mut positions, &mut smoothed);
+    }
+
+    for (vertex, position) in vertices.iter_mut().zip(positions) {
+        vertex.set_position(position.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::InnerSpace;
+
+    use super::*;
+    use crate::adjacency::build_adjacency;
+    use crate::icosphere::icosphere;
+
+    /// Variance of the distances from the center relative to their mean, which doesn't change
+    /// when the mesh shrinks uniformly.
+    fn relative_radius_variance(vertices: &[Vertex]) -> f32 {
+        let radii: Vec<f32> = vertices
+            .iter()
+            .map(|v| Vector3::from(v.position()).magnitude())
+            .collect();
+        let mean = radii.iter().sum::<f32>() / radii.len() as f32;
+        let variance = radii.iter().map(|r| (r - mean).powi(2)).sum::<f32>() / radii.len() as f32;
+        variance / (mean * mean)
+    }
+
+    #[test]
+    fn smoothing_evens_out_a_bumpy_sphere() {
+        let (mut vertices, indices) = icosphere(3);
+        for (i, vertex) in vertices.iter_mut().enumerate() {
+            let bump = 1.0 + 0.1 * (i as f32 * 1.7).sin();
+            let position = Vector3::from(vertex.position()) * bump;
+            vertex.set_position(position.into());
+        }
+        let before = relative_radius_variance(&vertices);
+        let adjacency = build_adjacency(&indices, vertices.len());
+        laplacian_smooth(&mut vertices, &adjacency, 5, 0.5);
+        let after = relative_radius_variance(&vertices);
+        assert!(after < 0.5 * before, "variance {} -> {}", before, after);
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use cgmath::Point3;
+
+/// The window title without any statistics.
+pub const TITLE: &str = "CurveBall";
+
+/// Rendering statistics which are accumulated over a reporting interval (i.e. one second).
+///
+/// All times are measured on the CPU. The GPU execution time would need timestamp queries,
+/// which vulkano's command buffer builder doesn't support, so the submit time is the closest
+/// measure of it: it includes waiting for the GPU when its queue is full.
+#[derive(Debug, Default)]
+pub struct FrameStats {
+    frames: u32,
+    /// Sum of all frame times in seconds
+    frame_time: f32,
+    /// Sums of the time spent in `acquire_next_image`, recording the command buffer and
+    /// submitting/presenting it, in seconds
+    acquire_time: f32,
+    record_time: f32,
+    submit_time: f32,
+    draw_calls: u32,
+    triangles: u64,
+    camera_pos: Option<Point3<f32>>,
+    /// How often each level of detail was drawn, counting every copy of the mesh
+    lods: Vec<u32>,
+}
+
+impl FrameStats {
+    pub fn add_frame(&mut self, frame_time: f32) {
+        self.frames += 1;
+        self.frame_time += frame_time;
+    }
+
+    pub fn add_acquire_time(&mut self, time: f32) {
+        self.acquire_time += time;
+    }
+
+    pub fn add_record_time(&mut self, time: f32) {
+        self.record_time += time;
+    }
+
+    pub fn add_submit_time(&mut self, time: f32) {
+        self.submit_time += time;
+    }
+
+    pub fn add_draw_call(&mut self, triangles: u64) {
+        self.draw_calls += 1;
+        self.triangles += triangles;
+    }
+
+    /// Records that `instances` copies of the mesh were drawn with the level `index`.
+    pub fn add_lod(&mut self, index: usize, num_lods: usize, instances: u32) {
+        self.lods.resize(num_lods, 0);
+        self.lods[index] += instances;
+    }
+
+    /// Triangles drawn so far in this interval.
+    pub fn triangles(&self) -> u64 {
+        self.triangles
+    }
+
+    pub fn set_camera_pos(&mut self, pos: Point3<f32>) {
+        self.camera_pos = Some(pos);
+    }
+
+    /// The window title with the frame rate and average frame time of the interval.
+    pub fn title(&self) -> String {
+        let frames = self.frames.max(1);
+        format!(
+            "{} — {} fps, {:.2} ms",
+            TITLE,
+            self.frames,
+            self.frame_time / frames as f32 * 1000.
+        )
+    }
+}
+
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Avoid NaNs when no frame was rendered at all, e.g. while the window is being resized
+        let frames = self.frames.max(1);
+        write!(
+            f,
+            "fps: {}, frame time: {:.2} ms (acquire: {:.2} ms, record: {:.2} ms, \
+             submit: {:.2} ms; GPU time not measured), draw calls: {}, triangles: {}",
+            self.frames,
+            self.frame_time / frames as f32 * 1000.,
+            self.acquire_time / frames as f32 * 1000.,
+            self.record_time / frames as f32 * 1000.,
+            self.submit_time / frames as f32 * 1000.,
+            self.draw_calls / frames,
+            self.triangles / u64::from(frames),
+        )?;
+        if let Some(pos) = self.camera_pos {
+            write!(f, ", camera: ({:.2}, {:.2}, {:.2})", pos.x, pos.y, pos.z)?;
+        }
+        if !self.lods.is_empty() {
+            write!(f, ", lods: {:?}", self.lods)?;
+        }
+        Ok(())
+    }
+}
+
+/// Minimum, maximum and average of a series of frame times.
+#[derive(Debug, Default)]
+pub struct FrameTimes {
+    count: u32,
+    /// All in seconds
+    sum: f32,
+    min: f32,
+    max: f32,
+}
+
+impl FrameTimes {
+    pub fn add(&mut self, frame_time: f32) {
+        if self.count == 0 {
+            self.min = frame_time;
+            self.max = frame_time;
+        } else {
+            self.min = self.min.min(frame_time);
+            self.max = self.max.max(frame_time);
+        }
+        self.count += 1;
+        self.sum += frame_time;
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+impl fmt::Display for FrameTimes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} frames, frame time: avg {:.2} ms, min {:.2} ms, max {:.2} ms",
+            self.count,
+            self.sum / self.count.max(1) as f32 * 1000.,
+            self.min * 1000.,
+            self.max * 1000.,
+        )
+    }
+}
+
+/// Writes one CSV row with the timings of every frame, for `--timing-csv`.
+pub struct TimingCsv {
+    writer: BufWriter<File>,
+    frame: u64,
+}
+
+impl TimingCsv {
+    /// Creates or truncates the file at `path` and writes the header row.
+    pub fn create(path: &Path) -> io::Result<TimingCsv> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "frame,frame_time_ms,record_time_ms,gpu_time_ms,triangles"
+        )?;
+        Ok(TimingCsv { writer, frame: 0 })
+    }
+
+    /// Appends a row for the next frame. Times are in seconds; GPU times aren't measured, so that
+    /// column is left empty.
+    pub fn add_frame(
+        &mut self,
+        frame_time: f32,
+        record_time: f32,
+        triangles: u64,
+    ) -> io::Result<()> {
+        writeln!(
+            self.writer,
+            "{},{:.3},{:.3},,{}",
+            self.frame,
+            frame_time * 1000.,
+            record_time * 1000.,
+            triangles
+        )?;
+        self.frame += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,98 @@
+use curveball::icosphere::MAX_SUBDIVISION_LEVEL;
+
+/// Frame rate below which a subdivision level is no longer considered sustainable.
+const MIN_FPS: f32 = 30.0;
+/// Time in seconds each level is rendered for before it is measured, to let the frame rate settle.
+const WARMUP: f32 = 0.5;
+/// Time in seconds each level is measured for.
+const MEASURE: f32 = 2.0;
+
+pub enum StressStep {
+    /// Keep rendering the current level.
+    Continue,
+    /// Switch to a mesh with the given subdivision level.
+    Level(u8),
+    /// The benchmark is finished.
+    Done,
+}
+
+/// Renders icospheres with increasing subdivision levels until the frame rate drops below
+/// [`MIN_FPS`] and keeps track of the highest triangle throughput on the way.
+///
+/// The frame rate is capped by the present mode, so with vsync the throughput only starts to
+/// reflect the GPU once a level can't be rendered at the refresh rate anymore.
+pub struct Stress {
+    level: u8,
+    elapsed: f32,
+    frames: u32,
+    /// Triangles submitted in the measured frames
+    triangles: u64,
+    /// Subdivision level, triangles and triangles per second of the highest throughput so far
+    best: Option<(u8, u64, f64)>,
+}
+
+impl Stress {
+    pub fn new() -> Stress {
+        Stress {
+            level: 0,
+            elapsed: 0.0,
+            frames: 0,
+            triangles: 0,
+            best: None,
+        }
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    /// Records a frame of the current level which took `frame_time` seconds and submitted
+    /// `triangles` triangles, with all copies of the mesh.
+    pub fn add_frame(&mut self, frame_time: f32, triangles: u64) -> StressStep {
+        self.elapsed += frame_time;
+        if self.elapsed <= WARMUP {
+            return StressStep::Continue;
+        }
+        self.frames += 1;
+        self.triangles += triangles;
+        if self.elapsed < WARMUP + MEASURE {
+            return StressStep::Continue;
+        }
+
+        let measured = self.elapsed - WARMUP;
+        let fps = self.frames as f32 / measured;
+        let throughput = self.triangles as f64 / f64::from(measured);
+        // Per frame
+        let triangles = self.triangles / u64::from(self.frames);
+        println!(
+            "stress: level {}, {} triangles, {:.1} fps, {:.0} triangles/s",
+            self.level, triangles, fps, throughput
+        );
+        if fps < MIN_FPS {
+            return StressStep::Done;
+        }
+        if self.best.map_or(true, |(_, _, best)| throughput > best) {
+            self.best = Some((self.level, triangles, throughput));
+        }
+        if self.level == MAX_SUBDIVISION_LEVEL {
+            return StressStep::Done;
+        }
+
+        self.level += 1;
+        self.elapsed = 0.0;
+        self.frames = 0;
+        self.triangles = 0;
+        StressStep::Level(self.level)
+    }
+
+    /// The final summary line.
+    pub fn summary(&self) -> String {
+        match self.best {
+            Some((level, triangles, throughput)) => format!(
+                "max sustained throughput: {:.0} triangles/s (level {}, {} triangles, at least {} fps)",
+                throughput, level, triangles, MIN_FPS
+            ),
+            None => format!("no level could be rendered at {} fps", MIN_FPS),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use vulkano::device::Queue;
+use vulkano::format::Format;
+use vulkano::image::{Dimensions, ImmutableImage};
+use vulkano::sync::GpuFuture;
+
+/// Width and height of the generated textures in texels.
+pub const TEXTURE_SIZE: u32 = 512;
+
+/// The texture the meshes are drawn with by default, which visualizes the texture coordinates:
+/// red grows with u and green with v. The texels are linear and have to be uploaded as
+/// [`Format::R8G8B8A8Unorm`].
+pub fn uv_gradient() -> Vec<[u8; 4]> {
+    let channel = |i: u32| ((i as f32 + 0.5) / TEXTURE_SIZE as f32 * 255.0).round() as u8;
+    texels(|x, y| [channel(x), channel(y), 128, 255])
+}
+
+/// A checkerboard of `tiles`×`tiles` squares alternating between the two sRGB `colors`, starting
+/// with the first one at u = v = 0, for checking the texture coordinates with `--uv-test`. It has
+/// to be uploaded as [`Format::R8G8B8A8Srgb`].
+pub fn checkerboard(tiles: u32, colors: [[u8; 3]; 2]) -> Vec<[u8; 4]> {
+    texels(|x, y| {
+        let tile = |i: u32| i * tiles / TEXTURE_SIZE;
+        let [r, g, b] = colors[((tile(x) + tile(y)) % 2) as usize];
+        [r, g, b, 255]
+    })
+}
+
+fn texels(texel: impl Fn(u32, u32) -> [u8; 4]) -> Vec<[u8; 4]> {
+    (0..TEXTURE_SIZE)
+        .flat_map(|y| (0..TEXTURE_SIZE).map(move |x| (x, y)))
+        .map(|(x, y)| texel(x, y))
+        .collect()
+}
+
+/// Uploads the `texels` of one of the textures above with `queue`. The image may only be sampled
+/// after the returned future.
+pub fn upload(
+    queue: Arc<Queue>,
+    texels: Vec<[u8; 4]>,
+    format: Format,
+) -> Result<(Arc<ImmutableImage<Format>>, Box<dyn GpuFuture>), String> {
+    let dimensions = Dimensions::Dim2d {
+        width: TEXTURE_SIZE,
+        height: TEXTURE_SIZE,
+    };
+    let (image, upload) = ImmutableImage::from_iter(texels.into_iter(), dimensions, format, queue)
+        .map_err(|err| format!("failed to create texture: {}", err))?;
+    Ok((image, Box::new(upload)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkerboard_alternates_between_tiles() {
+        let colors = [[255, 0, 0], [0, 0, 255]];
+        let texels = checkerboard(4, colors);
+        let tile = TEXTURE_SIZE / 4;
+        let at = |x: u32, y: u32| texels[(y * TEXTURE_SIZE + x) as usize];
+
+        assert_eq!(texels.len(), (TEXTURE_SIZE * TEXTURE_SIZE) as usize);
+        assert_eq!(at(0, 0), [255, 0, 0, 255]);
+        assert_eq!(at(tile - 1, tile - 1), [255, 0, 0, 255]);
+        assert_eq!(at(tile, 0), [0, 0, 255, 255]);
+        assert_eq!(at(0, tile), [0, 0, 255, 255]);
+        assert_eq!(at(tile, tile), [255, 0, 0, 255]);
+        assert_eq!(at(TEXTURE_SIZE - 1, 0), [0, 0, 255, 255]);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::adjacency::build_adjacency;
+use crate::bounds::{bounding_box, Aabb};
+use crate::mesh::triangle_areas;
+use crate::vertex::Vertex;
+
+/// The problems found in a mesh by [`validate`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub vertices: usize,
+    pub triangles: usize,
+    /// Number of indices after the last whole triangle.
+    pub trailing_indices: usize,
+    /// The triangles with an index that isn't smaller than the number of vertices.
+    pub out_of_range_triangles: Vec<usize>,
+    /// The vertices with a NaN or infinite coordinate.
+    pub invalid_vertices: Vec<usize>,
+    /// The triangles without area, e.g. because they use the same vertex twice.
+    pub degenerate_triangles: Vec<usize>,
+    /// The edges shared by more than two triangles, smaller vertex index first.
+    pub non_manifold_edges: Vec<(u32, u32)>,
+    /// Number of edges with only one triangle, which are fine for open meshes.
+    pub boundary_edges: usize,
+    /// The bounding box of the valid vertices; `None` if there are none.
+    pub bounds: Option<Aabb>,
+}
+
+impl Report {
+    /// Whether none of the problems were found; boundary edges don't count as one.
+    pub fn is_valid(&self) -> bool {
+        self.trailing_indices == 0
+            && self.out_of_range_triangles.is_empty()
+            && self.invalid_vertices.is_empty()
+            && self.degenerate_triangles.is_empty()
+            && self.non_manifold_edges.is_empty()
+    }
+}
+
+/// Checks the mesh for indices out of range, vertices that aren't finite, degenerate triangles
+/// and non-manifold edges. Triangles with an index out of range are left out of the other checks.
+pub fn validate(vertices: &[Vertex], indices: &[u32]) -> Report {
+    let trailing_indices = indices.len() % 3;
+    let faces = &indices[..indices.len() - trailing_indices];
+
+    let mut out_of_range_triangles = Vec::new();
+    let mut in_range = Vec::with_capacity(faces.len());
+    // Positions of the triangles in `in_range` in `faces`
+    let mut in_range_triangles = Vec::with_capacity(faces.len() / 3);
+    for (triangle, face) in faces.chunks(3).enumerate() {
+        if face.iter().any(|&i| i as usize >= vertices.len()) {
+            out_of_range_triangles.push(triangle);
+        } else {
+            in_range.extend_from_slice(face);
+            in_range_triangles.push(triangle);
+        }
+    }
+
+    let invalid_vertices: Vec<usize> = vertices
+        .iter()
+        .enumerate()
+        .filter(|(_, vertex)| !vertex.position().iter().all(|coord| coord.is_finite()))
+        .map(|(i, _)| i)
+        .collect();
+
+    // Triangles with invalid vertices have a NaN area and are only reported with the vertices
+    let degenerate_triangles = triangle_areas(vertices, &in_range)
+        .iter()
+        .zip(&in_range_triangles)
+        .filter(|(&area, _)| area == 0.0)
+        .map(|(_, &triangle)| triangle)
+        .collect();
+
+    let adjacency = build_adjacency(&in_range, vertices.len());
+    let mut non_manifold_edges: Vec<(u32, u32)> = adjacency
+        .edge_triangles
+        .iter()
+        .filter(|(_, triangles)| triangles.len() > 2)
+        .map(|(&edge, _)| edge)
+        .collect();
+    non_manifold_edges.sort();
+    let boundary_edges = adjacency
+        .edge_triangles
+        .values()
+        .filter(|triangles| triangles.len() == 1)
+        .count();
+
+    let valid_vertices: Vec<Vertex> = vertices
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| invalid_vertices.binary_search(i).is_err())
+        .map(|(_, vertex)| *vertex)
+        .collect();
+
+    Report {
+        vertices: vertices.len(),
+        triangles: faces.len() / 3,
+        trailing_indices,
+        out_of_range_triangles,
+        invalid_vertices,
+        degenerate_triangles,
+        non_manifold_edges,
+        boundary_edges,
+        bounds: bounding_box(&valid_vertices),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use super::*;
+    use crate::icosphere::icosphere;
+
+    #[test]
+    fn icosphere_is_valid() {
+        let (vertices, indices) = icosphere(2);
+        let report = validate(&vertices, &indices);
+        assert!(report.is_valid(), "{:?}", report);
+        assert_eq!(report.boundary_edges, 0);
+    }
+
+    #[test]
+    fn problems_are_reported() {
+        let vertices = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [0.0, -1.0, 0.0],
+            [std::f32::NAN, 0.0, 0.0],
+        ]
+        .iter()
+        .map(|&position| Vertex::from_position(position))
+        .collect::<Vec<_>>();
+        // Three triangles on the edge 0-1, one with the same vertex twice, one with an index out
+        // of range and one leftover index
+        let indices = [0, 1, 2, 1, 0, 3, 0, 1, 4, 2, 2, 3, 0, 1, 9, 5, 0, 1, 0];
+        let report = validate(&vertices, &indices);
+
+        assert!(!report.is_valid());
+        assert_eq!(report.triangles, 6);
+        assert_eq!(report.trailing_indices, 1);
+        assert_eq!(report.out_of_range_triangles, [4]);
+        assert_eq!(report.invalid_vertices, [5]);
+        assert_eq!(report.degenerate_triangles, [3]);
+        assert_eq!(report.non_manifold_edges, [(0, 1)]);
+        assert_eq!(report.bounds.unwrap().min, Vector3::new(0.0, -1.0, 0.0));
+    }
+}
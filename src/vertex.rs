@@ -1,18 +1,92 @@
 #![allow(clippy::ref_in_deref)]
 
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
 use cgmath::Vector3;
 use vulkano::impl_vertex;
 
-#[derive(Debug, Clone)]
+/// A mesh vertex as it is stored in the vertex buffer.
+///
+/// The struct is `#[repr(C)]` so its memory layout is fixed and can be described to other
+/// graphics APIs:
+///
+/// | attribute  | type       | offset (bytes) |
+/// |------------|------------|----------------|
+/// | `position` | `[f32; 3]` | 0              |
+/// | `normal`   | `[f32; 3]` | 12             |
+/// | `uv`       | `[f32; 2]` | 24             |
+///
+/// Vertices are tightly packed with a stride of [`Vertex::STRIDE`] bytes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
 pub struct Vertex {
     position: [f32; 3],
+    /// Unit length, pointing outwards.
+    normal: [f32; 3],
+    /// Texture coordinates with the origin at the top left of the texture.
+    uv: [f32; 2],
 }
-impl_vertex!(Vertex, position);
+impl_vertex!(Vertex, position, normal, uv);
 
-impl From<Vector3<f32>> for Vertex {
-    fn from(vec3: Vector3<f32>) -> Vertex {
+impl Vertex {
+    /// Size of a single vertex in bytes.
+    pub const STRIDE: usize = mem::size_of::<Vertex>();
+    /// Byte offset of the `position` attribute.
+    pub const POSITION_OFFSET: usize = 0;
+    /// Byte offset of the `normal` attribute.
+    pub const NORMAL_OFFSET: usize = 12;
+    /// Byte offset of the `uv` attribute.
+    pub const UV_OFFSET: usize = 24;
+
+    pub fn new(position: [f32; 3], normal: [f32; 3], uv: [f32; 2]) -> Vertex {
         Vertex {
-            position: [vec3.x, vec3.y, vec3.z],
+            position,
+            normal,
+            uv,
         }
     }
+
+    /// A vertex with a zero normal and zero texture coordinates, e.g. to fill in the normals with
+    /// [`compute_normals`](crate::mesh::compute_normals) later.
+    pub fn from_position(position: [f32; 3]) -> Vertex {
+        Vertex::new(position, [0.0; 3], [0.0; 2])
+    }
+
+    pub fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: [f32; 3]) {
+        self.position = position;
+    }
+
+    pub fn normal(&self) -> [f32; 3] {
+        self.normal
+    }
+
+    pub fn set_normal(&mut self, normal: [f32; 3]) {
+        self.normal = normal;
+    }
+
+    pub fn uv(&self) -> [f32; 2] {
+        self.uv
+    }
+
+    pub fn set_uv(&mut self, uv: [f32; 2]) {
+        self.uv = uv;
+    }
+}
+
+/// Same as [`Vertex::from_position`].
+impl From<[f32; 3]> for Vertex {
+    fn from(position: [f32; 3]) -> Vertex {
+        Vertex::from_position(position)
+    }
+}
+
+impl From<Vector3<f32>> for Vertex {
+    fn from(vec3: Vector3<f32>) -> Vertex {
+        Vertex::from_position(vec3.into())
+    }
 }